@@ -0,0 +1,210 @@
+//! Browsing the contents of trashed directories, and reading trashed files, without restoring them.
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::ErrorKind,
+    os::unix::fs::{MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use super::{ensure_virtually_exists, entry_of_trashed_item, fs_error, restorable_file_in_trash_from_info_file};
+use crate::{Error, TrashItem, TrashItemSize};
+
+/// An entry within a trashed directory, as returned by [`read_trashed_dir`](crate::os_limited::read_trashed_dir)
+/// and [`walk_trashed_dir`](crate::os_limited::walk_trashed_dir).
+#[derive(Debug, Clone)]
+pub struct TrashedEntry {
+    /// The path of the entry relative to the trashed directory, like `src/main.rs`, which can be passed to
+    /// [`restore_sub_path`](crate::os_limited::restore_sub_path).
+    pub path: PathBuf,
+    /// The type of the entry. Symbolic links aren't followed.
+    pub file_type: fs::FileType,
+    /// The size of a file in bytes, or the number of entries of a directory.
+    pub size: TrashItemSize,
+    /// The number of non-leap seconds elapsed between the UNIX Epoch and the last modification of the entry.
+    pub modified: i64,
+}
+
+impl TrashedEntry {
+    /// The name of the entry within its directory.
+    pub fn name(&self) -> OsString {
+        self.path.file_name().unwrap_or_default().to_owned()
+    }
+}
+
+/// Read-only access to the content of a trashed item or one of its entries, as returned by
+/// [`open`](crate::os_limited::open).
+#[derive(Debug)]
+pub enum TrashedContent {
+    /// A file, opened for reading.
+    File(fs::File),
+    /// The target of a symbolic link, which isn't followed.
+    Symlink(PathBuf),
+    /// A directory, opened for reading, like for `openat` and similar functions. See
+    /// [`read_trashed_dir`](crate::os_limited::read_trashed_dir) to read its entries.
+    Directory(fs::File),
+}
+
+pub(crate) fn open(item: &TrashItem, sub_path: &Path) -> Result<TrashedContent, Error> {
+    let file = restorable_file_in_trash_from_info_file(&item.id);
+    ensure_virtually_exists(&file)?;
+    let (path, metadata) = entry_of_trashed_item(&file, sub_path)?;
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        return fs::read_link(&path).map(TrashedContent::Symlink).map_err(|e| fs_error(&path, e));
+    }
+    if !file_type.is_file() && !file_type.is_dir() {
+        // Opening a FIFO would block until something writes to it, and devices aren't content of the trash.
+        let e = std::io::Error::new(ErrorKind::Unsupported, "only files, directories and symbolic links can be opened");
+        return Err(fs_error(&path, e));
+    }
+    // The entry could be replaced by a symbolic link in the meantime, which must not be followed.
+    let mut options = fs::OpenOptions::new();
+    options.read(true).custom_flags(libc::O_NOFOLLOW);
+    if file_type.is_dir() {
+        options.custom_flags(libc::O_NOFOLLOW | libc::O_DIRECTORY);
+    }
+    let opened = options.open(&path).map_err(|e| fs_error(&path, e))?;
+    Ok(if file_type.is_dir() { TrashedContent::Directory(opened) } else { TrashedContent::File(opened) })
+}
+
+pub(crate) fn read_trashed_dir(item: &TrashItem, sub_path: &Path) -> Result<Vec<TrashedEntry>, Error> {
+    let file = restorable_file_in_trash_from_info_file(&item.id);
+    ensure_virtually_exists(&file)?;
+    let (dir, metadata) = entry_of_trashed_item(&file, sub_path)?;
+    if !metadata.is_dir() {
+        return Err(fs_error(&dir, std::io::Error::new(ErrorKind::NotADirectory, "not a directory of the item")));
+    }
+    read_entries(&dir, sub_path)
+}
+
+pub(crate) fn walk_trashed_dir(item: &TrashItem) -> Result<Vec<TrashedEntry>, Error> {
+    let file = restorable_file_in_trash_from_info_file(&item.id);
+    let mut entries = Vec::new();
+    for entry in read_trashed_dir(item, Path::new(""))? {
+        walk(&file, entry, &mut entries)?;
+    }
+    Ok(entries)
+}
+
+/// Pushes `entry` to `entries`, followed by everything within it if it's a directory, depth first.
+fn walk(file: &Path, entry: TrashedEntry, entries: &mut Vec<TrashedEntry>) -> Result<(), Error> {
+    let children = if entry.file_type.is_dir() { read_entries(&file.join(&entry.path), &entry.path)? } else { vec![] };
+    entries.push(entry);
+    for child in children {
+        walk(file, child, entries)?;
+    }
+    Ok(())
+}
+
+/// Reads the entries of `dir`, whose path relative to the trashed item is `relative_path`, sorted by name.
+fn read_entries(dir: &Path, relative_path: &Path) -> Result<Vec<TrashedEntry>, Error> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| fs_error(dir, e))? {
+        let entry = entry.map_err(|e| fs_error(dir, e))?;
+        let path = entry.path();
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            // Another process may have removed the entry by now.
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(fs_error(&path, e)),
+        };
+        let size = if metadata.is_dir() {
+            TrashItemSize::Entries(fs::read_dir(&path).map_err(|e| fs_error(&path, e))?.count())
+        } else {
+            TrashItemSize::Bytes(metadata.len())
+        };
+        entries.push(TrashedEntry {
+            path: relative_path.join(entry.file_name()),
+            file_type: metadata.file_type(),
+            size,
+            modified: metadata.mtime(),
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use super::TrashedContent;
+    use crate::{
+        freedesktop::tests::isolated_context,
+        os_limited::{open, read_trashed_dir, walk_trashed_dir},
+        TrashItemSize,
+    };
+
+    #[test]
+    fn browse_trashed_dir() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        fs::create_dir_all(root.join("dir/sub/empty")).unwrap();
+        fs::write(root.join("dir/b"), "bb").unwrap();
+        fs::write(root.join("dir/sub/c"), "ccc").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("dir/a-link")).unwrap();
+        fs::write(root.join("file"), "").unwrap();
+        let mut items = ctx.delete_all_with_items([root.join("dir"), root.join("file")]).unwrap();
+        let file = items.pop().unwrap();
+        let dir = items.pop().unwrap();
+
+        let entries = read_trashed_dir(&dir, "").unwrap();
+        let names: Vec<_> = entries.iter().map(|entry| entry.name()).collect();
+        assert_eq!(names, ["a-link", "b", "sub"]);
+        assert!(entries[0].file_type.is_symlink());
+        assert_eq!(entries[1].size, TrashItemSize::Bytes(2));
+        assert_eq!(entries[2].size, TrashItemSize::Entries(2));
+        assert!(entries[1].modified > 0);
+
+        let sub: Vec<_> = read_trashed_dir(&dir, "sub").unwrap().into_iter().map(|entry| entry.path).collect();
+        assert_eq!(sub, [Path::new("sub/c"), Path::new("sub/empty")]);
+        for invalid in ["a-link", "b", "../dir", "missing"] {
+            assert!(read_trashed_dir(&dir, invalid).is_err(), "{invalid} is rejected");
+        }
+        assert!(read_trashed_dir(&file, "").is_err());
+
+        let walked: Vec<_> = walk_trashed_dir(&dir).unwrap().into_iter().map(|entry| entry.path).collect();
+        assert_eq!(walked, ["a-link", "b", "sub", "sub/c", "sub/empty"].map(PathBuf::from));
+        assert_eq!(ctx.list().unwrap().len(), 2, "nothing was restored");
+    }
+
+    #[test]
+    fn open_trashed() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        fs::create_dir_all(root.join("dir/sub")).unwrap();
+        fs::write(root.join("dir/sub/doc"), "document").unwrap();
+        std::os::unix::fs::symlink("sub/doc", root.join("dir/link")).unwrap();
+        std::os::unix::net::UnixListener::bind(root.join("dir/socket")).unwrap();
+        fs::write(root.join("file"), "content").unwrap();
+        let mut items = ctx.delete_all_with_items([root.join("dir"), root.join("file")]).unwrap();
+        let file = items.pop().unwrap();
+        let dir = items.pop().unwrap();
+
+        match open(&file, "").unwrap() {
+            TrashedContent::File(mut opened) => {
+                let mut content = String::new();
+                std::io::Read::read_to_string(&mut opened, &mut content).unwrap();
+                assert_eq!(content, "content");
+                assert!(std::io::Write::write_all(&mut opened, b"more").is_err(), "files are read-only");
+            }
+            other => panic!("expected a file, got {other:?}"),
+        }
+        match open(&dir, "sub/doc").unwrap() {
+            TrashedContent::File(opened) => assert_eq!(std::io::read_to_string(opened).unwrap(), "document"),
+            other => panic!("expected a file, got {other:?}"),
+        }
+        assert!(
+            matches!(open(&dir, "link").unwrap(), TrashedContent::Symlink(target) if target == Path::new("sub/doc"))
+        );
+        assert!(
+            matches!(open(&dir, "").unwrap(), TrashedContent::Directory(opened) if opened.metadata().unwrap().is_dir())
+        );
+        assert!(open(&dir, "socket").is_err());
+        assert!(open(&dir, "link/doc").is_err());
+        assert_eq!(ctx.list().unwrap().len(), 2, "nothing was restored");
+    }
+}
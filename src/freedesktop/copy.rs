@@ -0,0 +1,312 @@
+//! Copying of items across file systems, for when they can't be renamed into or out of a trash folder.
+//!
+//! Unlike `std::fs::copy`, this keeps everything that can be restored about an item: its type, including
+//! FIFOs, sockets and device nodes, its mode, owner, timestamps and extended attributes (which include ACLs),
+//! and hard links between files within the item.
+
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs::{self, Metadata},
+    io::{self, ErrorKind},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use super::{progress::ItemProgress, FsError};
+
+/// Copies `src` to `dst` along with all of its metadata, where `dst` is the placeholder created for `src`,
+/// so an empty directory if `src` is a directory and an empty file otherwise.
+pub(super) fn copy_item(src: &Path, dst: &Path, progress: &mut ItemProgress<'_>) -> Result<(), FsError> {
+    let metadata = src.symlink_metadata().map_err(|e| (src.to_owned(), e))?;
+    if !metadata.is_dir() && !metadata.is_file() {
+        // The placeholder is a regular file, and has to make room for what it's holding the place of.
+        fs::remove_file(dst).map_err(|e| (dst.to_owned(), e))?;
+    }
+    Copier { linked_files: HashMap::new(), progress }.copy(src, dst, &metadata, true)
+}
+
+struct Copier<'a, 'p> {
+    /// The copies of files with more than one hard link, by the device and inode of the original.
+    linked_files: HashMap<(u64, u64), PathBuf>,
+    progress: &'a mut ItemProgress<'p>,
+}
+
+impl Copier<'_, '_> {
+    fn copy(&mut self, src: &Path, dst: &Path, metadata: &Metadata, dst_exists: bool) -> Result<(), FsError> {
+        self.progress.check_cancelled().map_err(|e| (src.to_owned(), e))?;
+        let file_type = metadata.file_type();
+        if !file_type.is_dir() && metadata.nlink() > 1 {
+            if let Some(linked) = self.linked_files.get(&(metadata.dev(), metadata.ino())) {
+                if dst_exists {
+                    fs::remove_file(dst).map_err(|e| (dst.to_owned(), e))?;
+                }
+                fs::hard_link(linked, dst).map_err(|e| (dst.to_owned(), e))?;
+                self.progress.entry_done(0);
+                return Ok(());
+            }
+            self.linked_files.insert((metadata.dev(), metadata.ino()), dst.to_owned());
+        }
+
+        if file_type.is_dir() {
+            if !dst_exists {
+                fs::create_dir(dst).map_err(|e| (dst.to_owned(), e))?;
+            }
+            for entry in fs::read_dir(src).map_err(|e| (src.to_owned(), e))? {
+                let entry = entry.map_err(|e| (src.to_owned(), e))?;
+                let src_path = entry.path();
+                let metadata = src_path.symlink_metadata().map_err(|e| (src_path.clone(), e))?;
+                self.copy(&src_path, &dst.join(entry.file_name()), &metadata, false)?;
+            }
+        } else if file_type.is_file() {
+            // Unlike `fs::copy`, this doesn't copy the mode yet, which could keep the attributes from being set.
+            let mut reader = fs::File::open(src).map_err(|e| (src.to_owned(), e))?;
+            let mut writer = fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(dst)
+                .map_err(|e| (dst.to_owned(), e))?;
+            io::copy(&mut reader, &mut writer).map_err(|e| (src.to_owned(), e))?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(src).map_err(|e| (src.to_owned(), e))?;
+            std::os::unix::fs::symlink(target, dst).map_err(|e| (dst.to_owned(), e))?;
+        } else if file_type.is_fifo()
+            || file_type.is_socket()
+            || file_type.is_char_device()
+            || file_type.is_block_device()
+        {
+            let path = c_path(dst)?;
+            if unsafe { libc::mknod(path.as_ptr(), metadata.mode() as libc::mode_t, metadata.rdev() as libc::dev_t) }
+                != 0
+            {
+                return Err((dst.to_owned(), io::Error::last_os_error()));
+            }
+        } else {
+            return Err((src.to_owned(), io::Error::new(ErrorKind::Unsupported, "unknown file type")));
+        }
+        copy_metadata(src, dst, metadata).map_err(|e| (dst.to_owned(), e))?;
+        copy_times(dst, metadata).map_err(|e| (dst.to_owned(), e))?;
+        let bytes = if file_type.is_file() { metadata.len() } else { 0 };
+        self.progress.entry_done(bytes);
+        Ok(())
+    }
+}
+
+/// Copies the owner, extended attributes and mode, in this order as changing the owner clears the set-user-ID
+/// and set-group-ID bits as well as file capabilities, and a read-only mode keeps the owner from setting
+/// attributes.
+fn copy_metadata(src: &Path, dst: &Path, metadata: &Metadata) -> io::Result<()> {
+    match std::os::unix::fs::lchown(dst, Some(metadata.uid()), Some(metadata.gid())) {
+        Ok(()) => {}
+        // Only privileged processes may give files away, in which case they keep belonging to the current user
+        // just like with `cp --preserve`.
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            debug!("Could not preserve the owner of {:?}: {}", dst, e);
+        }
+        Err(e) => return Err(e),
+    }
+    copy_xattrs(src, dst)?;
+    if !metadata.file_type().is_symlink() {
+        fs::set_permissions(dst, fs::Permissions::from_mode(metadata.mode()))?;
+    }
+    Ok(())
+}
+
+/// Copies the access and modification times, which has to happen last as any other change to `dst`,
+/// like adding entries to a directory, updates them.
+fn copy_times(dst: &Path, metadata: &Metadata) -> io::Result<()> {
+    let path = c_path(dst).map_err(|(_, e)| e)?;
+    let times = [
+        libc::timespec { tv_sec: metadata.atime() as _, tv_nsec: metadata.atime_nsec() as _ },
+        libc::timespec { tv_sec: metadata.mtime() as _, tv_nsec: metadata.mtime_nsec() as _ },
+    ];
+    if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Copies all extended attributes that the destination file system supports and that the process may set.
+#[cfg(target_os = "linux")]
+fn copy_xattrs(src: &Path, dst: &Path) -> io::Result<()> {
+    let src_path = c_path(src).map_err(|(_, e)| e)?;
+    let dst_path = c_path(dst).map_err(|(_, e)| e)?;
+    let names = match read_xattr(|buf, len| unsafe { libc::llistxattr(src_path.as_ptr(), buf.cast(), len) }) {
+        Ok(names) => names,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in names.split(|b| *b == 0).filter(|name| !name.is_empty()) {
+        let name = CString::new(name).expect("names are nul-separated");
+        let value =
+            read_xattr(|buf, len| unsafe { libc::lgetxattr(src_path.as_ptr(), name.as_ptr(), buf.cast(), len) })?;
+        let res = unsafe { libc::lsetxattr(dst_path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+        if res != 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                // Attributes in the `trusted` and `security` namespaces need privileges, and other file systems
+                // may not support extended attributes at all.
+                Some(libc::EPERM | libc::ENOTSUP) => {
+                    debug!("Could not copy the attribute {:?} to {:?}: {}", name, dst, e)
+                }
+                _ => return Err(e),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Calls `read` first to learn the size of an attribute or attribute list, and then to read it.
+#[cfg(target_os = "linux")]
+fn read_xattr(mut read: impl FnMut(*mut u8, usize) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+    loop {
+        let len = read(std::ptr::null_mut(), 0);
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; len as usize];
+        let len = read(buf.as_mut_ptr(), buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+        let e = io::Error::last_os_error();
+        // The attribute grew in the meantime.
+        if e.raw_os_error() != Some(libc::ERANGE) {
+            return Err(e);
+        }
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString, FsError> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| (path.to_owned(), e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::CString,
+        fs,
+        os::unix::{
+            ffi::OsStrExt,
+            fs::{FileTypeExt, MetadataExt, PermissionsExt},
+        },
+        path::Path,
+    };
+
+    use super::copy_item;
+    use crate::freedesktop::progress::ItemProgress;
+
+    fn mkfifo(path: &Path) {
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o640) }, 0);
+    }
+
+    fn set_mtime(path: &Path, secs: i64) {
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let time = libc::timespec { tv_sec: secs as _, tv_nsec: 42 };
+        let times = [time, time];
+        assert_eq!(
+            unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) },
+            0
+        );
+    }
+
+    /// Returns `false` if the file system doesn't support extended attributes.
+    fn set_xattr(path: &Path, name: &str, value: &[u8]) -> bool {
+        let (c_path, name) = (CString::new(path.as_os_str().as_bytes()).unwrap(), CString::new(name).unwrap());
+        let res = unsafe { libc::lsetxattr(c_path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+        res == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::ENOTSUP) && panic!("lsetxattr failed")
+    }
+
+    fn get_xattr(path: &Path, name: &str) -> Vec<u8> {
+        let (c_path, name) = (CString::new(path.as_os_str().as_bytes()).unwrap(), CString::new(name).unwrap());
+        let mut buf = vec![0u8; 64];
+        let len = unsafe { libc::lgetxattr(c_path.as_ptr(), name.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        buf.truncate(usize::try_from(len).expect("the attribute exists"));
+        buf
+    }
+
+    #[test]
+    fn copy_preserves_metadata() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("file"), "content").unwrap();
+        fs::set_permissions(src.join("file"), fs::Permissions::from_mode(0o604)).unwrap();
+        fs::hard_link(src.join("file"), src.join("sub/link")).unwrap();
+        std::os::unix::fs::symlink("../file", src.join("sub/symlink")).unwrap();
+        mkfifo(&src.join("fifo"));
+        let xattrs_supported = set_xattr(&src.join("file"), "user.trash-test", b"value");
+        fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o750)).unwrap();
+        for (path, mtime) in
+            [("file", 1_000_000_000), ("sub/symlink", 1_100_000_000), ("sub", 1_200_000_000), ("", 1_300_000_000)]
+        {
+            set_mtime(&src.join(path), mtime);
+        }
+
+        let dst = tmp.path().join("dst");
+        fs::create_dir(&dst).unwrap();
+        copy_item(&src, &dst, &mut ItemProgress::untracked(&src)).unwrap();
+
+        for path in ["", "file", "sub", "sub/link", "sub/symlink", "fifo"] {
+            let (src, dst) = (src.join(path).symlink_metadata().unwrap(), dst.join(path).symlink_metadata().unwrap());
+            assert_eq!(src.file_type(), dst.file_type(), "{path}");
+            assert_eq!(src.mode(), dst.mode(), "{path}");
+            assert_eq!((src.uid(), src.gid()), (dst.uid(), dst.gid()), "{path}");
+            assert_eq!((src.mtime(), src.mtime_nsec()), (dst.mtime(), dst.mtime_nsec()), "{path}");
+        }
+        assert_eq!(fs::read_to_string(dst.join("sub/link")).unwrap(), "content");
+        assert_eq!(fs::read_link(dst.join("sub/symlink")).unwrap(), Path::new("../file"));
+        assert!(dst.join("fifo").symlink_metadata().unwrap().file_type().is_fifo());
+        if xattrs_supported {
+            assert_eq!(get_xattr(&dst.join("file"), "user.trash-test"), b"value");
+        }
+        let (file, link) = (dst.join("file").metadata().unwrap(), dst.join("sub/link").metadata().unwrap());
+        assert_eq!(file.ino(), link.ino(), "hard links stay together");
+    }
+
+    #[test]
+    fn copy_read_only_file_with_xattrs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("read-only");
+        fs::write(&src, "content").unwrap();
+        if !set_xattr(&src, "user.trash-test", b"value") {
+            return;
+        }
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o444)).unwrap();
+
+        // Unlike root, other users can't set attributes once the copy is read-only.
+        let dst = tmp.path().join("copy");
+        fs::File::create_new(&dst).unwrap();
+        copy_item(&src, &dst, &mut ItemProgress::untracked(&src)).unwrap();
+        assert_eq!(get_xattr(&dst, "user.trash-test"), b"value");
+        assert_eq!(dst.metadata().unwrap().mode() & 0o777, 0o444);
+    }
+
+    #[test]
+    fn copy_replaces_placeholder_of_special_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (fifo, symlink) = (tmp.path().join("fifo"), tmp.path().join("symlink"));
+        mkfifo(&fifo);
+        std::os::unix::fs::symlink("target", &symlink).unwrap();
+        for src in [fifo, symlink] {
+            let dst = tmp.path().join("copy");
+            fs::File::create_new(&dst).unwrap();
+            copy_item(&src, &dst, &mut ItemProgress::untracked(&src)).unwrap();
+            assert_eq!(src.symlink_metadata().unwrap().file_type(), dst.symlink_metadata().unwrap().file_type());
+            fs::remove_file(&dst).unwrap();
+        }
+    }
+}
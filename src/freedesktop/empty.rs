@@ -0,0 +1,174 @@
+//! Emptying trash folders entirely, including the entries that aren't proper trash items.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use super::{
+    eval_trash_folders, fs_error, is_cancelled_error, is_same_path, lock_trash_folder, remove_dir_all_with_progress,
+    EvaluatedTrashFolders, DIRECTORY_SIZES_FILE_NAME,
+};
+use crate::{Error, ItemError, ItemErrorKind, TrashContext};
+
+pub(crate) fn empty(ctx: &TrashContext) -> Result<Vec<ItemError>, Error> {
+    let EvaluatedTrashFolders { trash_folders, .. } = eval_trash_folders(ctx)?;
+    let mut errors = Vec::new();
+    for folder in trash_folders {
+        empty_trash_folder(ctx, &folder, &mut errors)?;
+    }
+    Ok(errors)
+}
+
+pub(crate) fn empty_folder(ctx: &TrashContext, folder: &Path) -> Result<Vec<ItemError>, Error> {
+    let EvaluatedTrashFolders { trash_folders, .. } = eval_trash_folders(ctx)?;
+    let Some(folder) = trash_folders.iter().find(|candidate| is_same_path(candidate, folder)) else {
+        let e = std::io::Error::new(ErrorKind::InvalidInput, "not a trash folder of the trash context");
+        return Err(fs_error(folder, e));
+    };
+    let mut errors = Vec::new();
+    empty_trash_folder(ctx, folder, &mut errors)?;
+    Ok(errors)
+}
+
+/// Removes everything in the `files` and `info` folders of `trash_folder`, as well as its directory size cache.
+/// Entries that can't be removed are pushed to `errors`, and only cancellation stops early.
+fn empty_trash_folder(ctx: &TrashContext, trash_folder: &Path, errors: &mut Vec<ItemError>) -> Result<(), Error> {
+    let files_folder = trash_folder.join("files");
+    // The items are removed before their info files, so that an interruption leaves orphans rather than
+    // info files of items that are gone.
+    for folder in [files_folder.clone(), trash_folder.join("info")] {
+        for path in read_entries(&folder, errors) {
+            if let Some(item) = item_of_info_file(&files_folder, &path) {
+                if item.symlink_metadata().is_ok() {
+                    debug!("Keeping {:?}, as its item {:?} couldn't be removed", path, item);
+                    continue;
+                }
+            }
+            let mut progress = ctx.platform_specific.item_progress(&path);
+            progress.check_cancelled().map_err(|e| fs_error(&path, e))?;
+            let removal = match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => remove_dir_all_with_progress(&path, &mut progress),
+                Ok(metadata) => {
+                    fs::remove_file(&path).map(|()| progress.entry_done(metadata.len())).map_err(|e| (path.clone(), e))
+                }
+                Err(e) => Err((path.clone(), e)),
+            };
+            match removal {
+                Ok(()) => progress.done(),
+                // Another process may have removed the entry by now.
+                Err((_, e)) if e.kind() == ErrorKind::NotFound => progress.done(),
+                Err((p, e)) if is_cancelled_error(&e) => return Err(fs_error(p, e)),
+                Err((p, e)) => errors.push(ItemError { path: p, kind: ItemErrorKind::Io(e) }),
+            }
+        }
+    }
+
+    let directory_sizes = trash_folder.join(DIRECTORY_SIZES_FILE_NAME);
+    let reset = lock_trash_folder(trash_folder).and_then(|_lock| match fs::remove_file(&directory_sizes) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err((directory_sizes.clone(), e)),
+        _ => Ok(()),
+    });
+    if let Err((path, e)) = reset {
+        errors.push(ItemError { path, kind: ItemErrorKind::Io(e) });
+    }
+    Ok(())
+}
+
+/// Returns the path of the item in `files_folder` that `path` is the info file of, if it is one.
+fn item_of_info_file(files_folder: &Path, path: &Path) -> Option<PathBuf> {
+    (path.extension()? == "trashinfo").then_some(files_folder.join(path.file_stem()?))
+}
+
+/// Returns the paths of the entries of `folder`, and pushes the errors of reading it to `errors`. A missing
+/// folder has no entries.
+fn read_entries(folder: &Path, errors: &mut Vec<ItemError>) -> Vec<PathBuf> {
+    let read_dir = match fs::read_dir(folder) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            errors.push(ItemError { path: folder.to_owned(), kind: ItemErrorKind::Io(e) });
+            return Vec::new();
+        }
+    };
+    let mut paths = Vec::new();
+    for entry in read_dir {
+        match entry {
+            Ok(entry) => paths.push(entry.path()),
+            Err(e) => errors.push(ItemError { path: folder.to_owned(), kind: ItemErrorKind::Io(e) }),
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use crate::{freedesktop::tests::isolated_context, os_limited::metadata};
+
+    #[test]
+    fn empty() {
+        let (_tmp, home, ctx) = isolated_context(&["mnt"]);
+        let topdir = home.join("mnt");
+        fs::create_dir_all(home.join("dir/sub")).unwrap();
+        fs::write(home.join("dir/sub/file"), "content").unwrap();
+        fs::write(home.join("file"), "content").unwrap();
+        fs::write(topdir.join("file"), "content").unwrap();
+        let items = ctx.delete_all_with_items([home.join("dir"), home.join("file"), topdir.join("file")]).unwrap();
+        metadata(&items[0]).unwrap();
+        let home_trash = home.join("Trash");
+        assert!(home_trash.join("directorysizes").is_file());
+        // An orphan, an unparsable info file and a dangling one.
+        fs::write(home_trash.join("files/orphan"), "").unwrap();
+        fs::write(home_trash.join("info/unparsable.trashinfo"), "garbage").unwrap();
+        fs::write(home_trash.join("info/dangling.trashinfo"), "[Trash Info]\nPath=/dangling\n").unwrap();
+
+        let uid = unsafe { libc::getuid() };
+        let topdir_trash = topdir.join(format!(".Trash-{uid}"));
+        assert!(ctx.empty_folder(&topdir).is_err(), "only trash folders can be emptied");
+        assert!(ctx.empty_folder(&topdir_trash).unwrap().is_empty());
+        assert!(topdir_trash.join("files").read_dir().unwrap().next().is_none());
+        assert_eq!(ctx.list().unwrap().len(), 3, "the items of the home trash, including the dangling one");
+
+        assert!(ctx.empty().unwrap().is_empty());
+        for folder in ["files", "info"] {
+            assert!(home_trash.join(folder).read_dir().unwrap().next().is_none(), "{folder} is empty");
+        }
+        assert!(!home_trash.join("directorysizes").exists());
+        assert!(ctx.is_empty().unwrap());
+        assert!(ctx.empty().unwrap().is_empty(), "an empty trash can be emptied again");
+    }
+
+    #[test]
+    fn empty_reports_failures() {
+        if unsafe { libc::getuid() } == 0 {
+            // Permissions don't stop root from removing entries.
+            return;
+        }
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        fs::create_dir_all(root.join("locked")).unwrap();
+        fs::write(root.join("locked/file"), "").unwrap();
+        fs::write(root.join("file"), "").unwrap();
+        ctx.delete_all([root.join("locked"), root.join("file")]).unwrap();
+        let locked = root.join("Trash/files/locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o500)).unwrap();
+
+        let errors = ctx.empty().unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o700)).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, locked.join("file"));
+        assert!(!root.join("Trash/files/file").exists(), "emptying continues after a failure");
+        assert!(!root.join("Trash/info/file.trashinfo").exists());
+        assert!(root.join("Trash/info/locked.trashinfo").exists(), "the info file of what's left is kept");
+        let items = ctx.list().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "locked");
+
+        assert!(ctx.empty().unwrap().is_empty(), "emptying can be retried");
+        assert!(ctx.is_empty().unwrap());
+    }
+}
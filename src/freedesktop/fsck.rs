@@ -0,0 +1,545 @@
+//! Consistency checks of trash folders, which find and optionally repair the leftovers of
+//! interrupted trash operations and of other programs manipulating the trash.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::{debug, warn};
+
+use super::{
+    eval_trash_folders, folder_validity, move_items_no_replace, progress::ItemProgress, remove_from_directory_sizes,
+    DeletionDate, DirectorySizeCache, EvaluatedTrashFolders, TrashInfo, TrashInfoError, TrashValidity,
+};
+use crate::{Error, TrashContext};
+
+/// The name of the folder within a trash folder that [`FsckRepair::LostAndFound`] moves entries to.
+pub const LOST_AND_FOUND_FOLDER_NAME: &str = "lost+found";
+
+/// How old a `.trashinfo` file without an item has to be to count as dangling. Info files are written before
+/// their item is moved into `$trash/files`, so younger ones may belong to an item that is being trashed right now.
+const DANGLING_INFO_GRACE_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+/// What [`fsck`](crate::os_limited::fsck) does about the problems it finds.
+///
+/// Any repair also adds the trashed directories that are missing from the directory size cache of their trash
+/// folder, so that [`metadata`](crate::os_limited::metadata) knows their size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FsckRepair {
+    /// Only report problems, and don't change anything. This is the default.
+    #[default]
+    None,
+    /// Write a new `.trashinfo` file for orphans and for items whose `.trashinfo` file can't be parsed,
+    /// so that they show up in the trash and can be restored into `original_parent`.
+    /// Dangling `.trashinfo` files are deleted.
+    RecreateInfo { original_parent: PathBuf },
+    /// Permanently delete orphans, dangling `.trashinfo` files, and unparsable `.trashinfo` files
+    /// along with their items.
+    Delete,
+    /// Move orphans, as well as unparsable `.trashinfo` files along with their items, into the
+    /// [`lost+found`](LOST_AND_FOUND_FOLDER_NAME) folder of their trash folder, where they are out of the way
+    /// but can still be inspected. Dangling `.trashinfo` files are deleted.
+    LostAndFound,
+}
+
+/// A problem found by [`fsck`](crate::os_limited::fsck).
+#[derive(Debug)]
+pub enum FsckProblem {
+    /// An entry of `$trash/files` without a `.trashinfo` file, usually left behind by other programs.
+    Orphan { path: PathBuf },
+    /// A `.trashinfo` file without an entry in `$trash/files`, usually left behind by a crash while
+    /// an item was put into the trash. Recently written ones aren't reported, as their item may still be on
+    /// its way into the trash.
+    DanglingInfo { info_path: PathBuf },
+    /// A `.trashinfo` file that can't be parsed, along with the entry of `$trash/files` it belongs to, if there is one.
+    UnparsableInfo { info_path: PathBuf, error: TrashInfoError, path: Option<PathBuf> },
+    /// A file or directory within a trash folder that couldn't be read.
+    Unreadable { path: PathBuf, error: std::io::Error },
+    /// A trash folder that can't be used. These are never repaired.
+    InvalidTrashFolder { path: PathBuf, reason: InvalidTrashFolder },
+}
+
+/// The reason a trash folder can't be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidTrashFolder {
+    /// An administrator-created `$topdir/.Trash` is a symbolic link.
+    Symlink,
+    /// An administrator-created `$topdir/.Trash` doesn't have the sticky bit set.
+    NotSticky,
+    /// The trash folder, or its `files` or `info` folder, is not a directory.
+    NotADirectory,
+}
+
+/// A problem found by [`fsck`](crate::os_limited::fsck), along with the outcome of its repair.
+#[derive(Debug)]
+pub struct FsckFinding {
+    pub problem: FsckProblem,
+    /// `None` if no repair was attempted, either because only a report was requested, because the
+    /// problem can't be repaired, or because the trash folder couldn't be read completely, in which case
+    /// the problem may not be one. Otherwise the result of the repair.
+    pub repair: Option<Result<(), std::io::Error>>,
+}
+
+pub(crate) fn fsck(ctx: &TrashContext, repair: &FsckRepair) -> Result<Vec<FsckFinding>, Error> {
+    let mut findings = Vec::new();
+    let invalid =
+        |path: PathBuf, reason| FsckFinding { problem: FsckProblem::InvalidTrashFolder { path, reason }, repair: None };
+
+    // Trash folders that are invalid are not part of the trash folders, so they are looked for separately.
+    if let Ok(home_trash) = ctx.platform_specific.home_trash() {
+        if home_trash.symlink_metadata().is_ok() && !home_trash.is_dir() {
+            findings.push(invalid(home_trash, InvalidTrashFolder::NotADirectory));
+        }
+    }
+    for mount in ctx.platform_specific.sorted_mount_points()? {
+        let admin_trash = mount.mnt_dir.join(".Trash");
+        if !admin_trash.is_dir() {
+            continue;
+        }
+        match folder_validity(&admin_trash) {
+            Ok(TrashValidity::Valid) => {}
+            Ok(TrashValidity::InvalidSymlink) => findings.push(invalid(admin_trash, InvalidTrashFolder::Symlink)),
+            Ok(TrashValidity::InvalidNotSticky) => findings.push(invalid(admin_trash, InvalidTrashFolder::NotSticky)),
+            Err((path, error)) => {
+                findings.push(FsckFinding { problem: FsckProblem::Unreadable { path, error }, repair: None })
+            }
+        }
+    }
+
+    let EvaluatedTrashFolders { trash_folders, .. } = eval_trash_folders(ctx)?;
+    for trash_folder in trash_folders {
+        fsck_trash_folder(&trash_folder, repair, &mut findings);
+    }
+    Ok(findings)
+}
+
+/// Checks a single trash folder, appending what it finds to `findings`.
+fn fsck_trash_folder(trash_folder: &Path, repair: &FsckRepair, findings: &mut Vec<FsckFinding>) {
+    let files_folder = trash_folder.join("files");
+    let info_folder = trash_folder.join("info");
+    for folder in [&files_folder, &info_folder] {
+        if folder.symlink_metadata().is_ok() && !folder.is_dir() {
+            findings.push(FsckFinding {
+                problem: FsckProblem::InvalidTrashFolder {
+                    path: trash_folder.to_owned(),
+                    reason: InvalidTrashFolder::NotADirectory,
+                },
+                repair: None,
+            });
+            return;
+        }
+    }
+    let entries = read_trash_folder(&files_folder, &info_folder, findings);
+    check_trash_folder(trash_folder, entries, repair, findings);
+}
+
+/// The entries of a trash folder, as far as they could be read.
+struct TrashFolderEntries {
+    /// The names of the entries of `$trash/files`.
+    items: HashSet<OsString>,
+    /// Whether all of `$trash/files` could be read. If not, an info file without an item may still have one.
+    all_items_read: bool,
+    /// The names of items in `$trash/files`, associated with the result of parsing their info file.
+    infos: HashMap<OsString, Result<TrashInfo, TrashInfoError>>,
+    /// The names of items whose info file couldn't be read.
+    unreadable_infos: HashSet<OsString>,
+    /// Whether all of `$trash/info` could be read. If not, an item without an info file may still have one.
+    all_infos_read: bool,
+}
+
+/// Reads the items and info files of a trash folder, appending what can't be read to `findings`.
+fn read_trash_folder(files_folder: &Path, info_folder: &Path, findings: &mut Vec<FsckFinding>) -> TrashFolderEntries {
+    let unreadable =
+        |path: PathBuf, error| FsckFinding { problem: FsckProblem::Unreadable { path, error }, repair: None };
+
+    // The items are read before the info files, as those are written first when an item is put into the trash.
+    // Otherwise an item that is trashed in between would appear to be an orphan.
+    let mut items = HashSet::new();
+    let mut all_items_read = true;
+    for entry in read_dir_if_exists(files_folder) {
+        match entry {
+            Ok(entry) => {
+                items.insert(entry.file_name());
+            }
+            Err((path, error)) => {
+                all_items_read = false;
+                findings.push(unreadable(path, error));
+            }
+        }
+    }
+
+    let mut infos = HashMap::new();
+    let mut unreadable_infos = HashSet::new();
+    let mut all_infos_read = true;
+    for entry in read_dir_if_exists(info_folder) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err((path, error)) => {
+                all_infos_read = false;
+                findings.push(unreadable(path, error));
+                continue;
+            }
+        };
+        let info_path = entry.path();
+        let Some(name) = info_file_name_to_item_name(&entry.file_name()) else {
+            debug!("Ignoring {:?} as it's not a trash info file", info_path);
+            continue;
+        };
+        match fs::read(&info_path) {
+            Ok(content) => {
+                infos.insert(name, TrashInfo::parse(&content));
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                all_infos_read = false;
+                unreadable_infos.insert(name);
+                findings.push(unreadable(info_path, error));
+            }
+        }
+    }
+    TrashFolderEntries { items, all_items_read, infos, unreadable_infos, all_infos_read }
+}
+
+/// Finds and repairs the problems among the entries of a trash folder, appending them to `findings`.
+///
+/// Nothing is repaired that depends on the absence of an entry which might just not have been read: orphans are
+/// left alone unless all info files were read, and info files without an item unless all items were read.
+fn check_trash_folder(
+    trash_folder: &Path,
+    entries: TrashFolderEntries,
+    repair: &FsckRepair,
+    findings: &mut Vec<FsckFinding>,
+) {
+    let files_folder = trash_folder.join("files");
+    let info_folder = trash_folder.join("info");
+    let TrashFolderEntries { items, all_items_read, infos, unreadable_infos, all_infos_read } = entries;
+
+    for name in &items {
+        // Items whose info file couldn't be read are already reported as unreadable.
+        if infos.contains_key(name) || unreadable_infos.contains(name) {
+            continue;
+        }
+        let path = files_folder.join(name);
+        let outcome = match repair {
+            _ if !all_infos_read => None,
+            FsckRepair::None => None,
+            FsckRepair::RecreateInfo { original_parent } => {
+                Some(write_info(&info_folder.join(info_file_name(name)), original_parent, name, true))
+            }
+            FsckRepair::Delete => Some(remove_item(&path)),
+            FsckRepair::LostAndFound => Some(move_to_lost_and_found(trash_folder, &path)),
+        };
+        findings.push(FsckFinding { problem: FsckProblem::Orphan { path }, repair: outcome });
+    }
+
+    // Directories that are missing from the directory size cache are measured, so that their size is known to
+    // `metadata`.
+    if *repair != FsckRepair::None {
+        let mut cache = DirectorySizeCache::read(trash_folder);
+        for name in infos.iter().filter(|(name, info)| info.is_ok() && items.contains(*name)).map(|(name, _)| name) {
+            let info_path = info_folder.join(info_file_name(name));
+            if let Err(e) = cache.disk_usage(&info_path) {
+                debug!("Could not determine the size of the item of {:?}: {:?}", info_path, e);
+            }
+        }
+        cache.write(trash_folder);
+    }
+
+    for (name, info) in infos {
+        let info_path = info_folder.join(info_file_name(&name));
+        let path = files_folder.join(&name);
+        let has_item = items.contains(&name);
+        let finding = match info {
+            Ok(_) if has_item => continue,
+            Ok(_) if is_within_grace_period(&info_path) => {
+                debug!("Ignoring {:?} without an item, as the item may be put into the trash right now", info_path);
+                continue;
+            }
+            Ok(_) => FsckFinding {
+                problem: FsckProblem::DanglingInfo { info_path: info_path.clone() },
+                repair: match repair {
+                    _ if !all_items_read => None,
+                    FsckRepair::None => None,
+                    _ => Some(fs::remove_file(&info_path)),
+                },
+            },
+            Err(error) => {
+                let outcome = match repair {
+                    _ if !has_item && !all_items_read => None,
+                    FsckRepair::None => None,
+                    FsckRepair::RecreateInfo { original_parent } if has_item => {
+                        Some(write_info(&info_path, original_parent, &name, false))
+                    }
+                    FsckRepair::RecreateInfo { .. } => Some(fs::remove_file(&info_path)),
+                    FsckRepair::Delete => Some(
+                        if has_item { remove_item(&path) } else { Ok(()) }.and_then(|_| fs::remove_file(&info_path)),
+                    ),
+                    FsckRepair::LostAndFound => Some(move_to_lost_and_found(trash_folder, &info_path).and_then(|_| {
+                        if has_item {
+                            move_to_lost_and_found(trash_folder, &path)
+                        } else {
+                            Ok(())
+                        }
+                    })),
+                };
+                FsckFinding {
+                    problem: FsckProblem::UnparsableInfo { info_path, error, path: has_item.then_some(path) },
+                    repair: outcome,
+                }
+            }
+        };
+        findings.push(finding);
+    }
+}
+
+/// Returns whether `path` was modified less than [`DANGLING_INFO_GRACE_PERIOD`] ago, or in the future.
+fn is_within_grace_period(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| !modified.elapsed().is_ok_and(|age| age >= DANGLING_INFO_GRACE_PERIOD))
+}
+
+fn read_dir_if_exists(folder: &Path) -> impl Iterator<Item = Result<fs::DirEntry, (PathBuf, std::io::Error)>> + '_ {
+    let (entries, error) = match fs::read_dir(folder) {
+        Ok(entries) => (Some(entries), None),
+        Err(e) if e.kind() == ErrorKind::NotFound => (None, None),
+        Err(e) => (None, Some(Err((folder.to_owned(), e)))),
+    };
+    error.into_iter().chain(entries.into_iter().flatten().map(move |entry| entry.map_err(|e| (folder.to_owned(), e))))
+}
+
+/// Returns the name of the item in `$trash/files` that the info file `file_name` belongs to.
+fn info_file_name_to_item_name(file_name: &OsStr) -> Option<OsString> {
+    let path = Path::new(file_name);
+    (path.extension()? == "trashinfo").then(|| path.file_stem().map(ToOwned::to_owned)).flatten()
+}
+
+fn info_file_name(name: &OsStr) -> OsString {
+    let mut info_name = name.to_owned();
+    info_name.push(".trashinfo");
+    info_name
+}
+
+/// Writes an info file for the item `name` that restores it into `original_parent`.
+fn write_info(info_path: &Path, original_parent: &Path, name: &OsStr, create_new: bool) -> std::io::Result<()> {
+    let info = TrashInfo { path: original_parent.join(name), deletion_date: DeletionDate::now(), unknown: Vec::new() };
+    if create_new {
+        use std::io::Write;
+        fs::OpenOptions::new().create_new(true).write(true).open(info_path)?.write_all(info.serialize().as_bytes())
+    } else {
+        fs::write(info_path, info.serialize())
+    }
+}
+
+/// Permanently deletes `path`, an entry of `$trash/files`.
+fn remove_item(path: &Path) -> std::io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)?;
+        remove_from_directory_sizes(path);
+        Ok(())
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Moves `path` into the `lost+found` folder of `trash_folder`, without replacing anything that's already there.
+fn move_to_lost_and_found(trash_folder: &Path, path: &Path) -> std::io::Result<()> {
+    let lost_and_found = trash_folder.join(LOST_AND_FOUND_FOLDER_NAME);
+    fs::create_dir_all(&lost_and_found)?;
+    let is_dir = path.symlink_metadata()?.is_dir();
+    let name = path.file_name().ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "path has no name"))?;
+    for appendage in 1usize.. {
+        let mut target_name = name.to_owned();
+        if appendage > 1 {
+            target_name.push(format!(".{appendage}"));
+        }
+        match move_items_no_replace(path, lost_and_found.join(&target_name), &mut ItemProgress::untracked(path)) {
+            Ok(()) => {
+                if is_dir {
+                    remove_from_directory_sizes(path);
+                }
+                return Ok(());
+            }
+            Err((_, e)) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err((failed_path, e)) => {
+                warn!("Failed to move {:?} to {:?}, the error occurred at {:?}", path, lost_and_found, failed_path);
+                return Err(e);
+            }
+        }
+    }
+    unreachable!("there is always a free name eventually")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::SystemTime};
+
+    use super::{
+        check_trash_folder, fsck_trash_folder, read_trash_folder, FsckFinding, FsckProblem, FsckRepair,
+        DANGLING_INFO_GRACE_PERIOD, LOST_AND_FOUND_FOLDER_NAME,
+    };
+    use crate::freedesktop::{TrashInfo, TrashInfoError};
+
+    /// Creates a trash folder with one healthy item, an orphan, a dangling info file, and an unparsable
+    /// info file which belongs to an item.
+    fn messy_trash_folder() -> tempfile::TempDir {
+        let trash = tempfile::tempdir().unwrap();
+        let (files, info) = (trash.path().join("files"), trash.path().join("info"));
+        fs::create_dir_all(files.join("orphan-dir")).unwrap();
+        fs::create_dir_all(&info).unwrap();
+        fs::write(files.join("healthy"), "").unwrap();
+        fs::write(info.join("healthy.trashinfo"), "[Trash Info]\nPath=/tmp/healthy\n").unwrap();
+        fs::write(files.join("orphan-dir/content"), "").unwrap();
+        fs::write(info.join("dangling.trashinfo"), "[Trash Info]\nPath=/tmp/dangling\n").unwrap();
+        let old = SystemTime::now() - DANGLING_INFO_GRACE_PERIOD * 2;
+        fs::File::options().write(true).open(info.join("dangling.trashinfo")).unwrap().set_modified(old).unwrap();
+        fs::write(files.join("unparsable"), "").unwrap();
+        fs::write(info.join("unparsable.trashinfo"), "garbage").unwrap();
+        fs::write(info.join("README"), "not an info file").unwrap();
+        trash
+    }
+
+    fn names(folder: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(folder)
+            .map(|entries| entries.map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    fn check(trash: &Path, repair: &FsckRepair) -> Vec<FsckFinding> {
+        let mut findings = Vec::new();
+        fsck_trash_folder(trash, repair, &mut findings);
+        assert_eq!(findings.len(), 3, "{findings:#?}");
+        assert!(findings.iter().all(|finding| finding.repair.as_ref().is_none_or(|result| result.is_ok())));
+        findings
+    }
+
+    #[test]
+    fn report_only() {
+        let trash = messy_trash_folder();
+        let findings = check(trash.path(), &FsckRepair::None);
+
+        assert!(findings.iter().all(|finding| finding.repair.is_none()));
+        assert!(findings
+            .iter()
+            .any(|f| matches!(&f.problem, FsckProblem::Orphan { path } if path.ends_with("files/orphan-dir"))));
+        assert!(findings
+            .iter()
+            .any(|f| matches!(&f.problem, FsckProblem::DanglingInfo { info_path } if info_path.ends_with("dangling.trashinfo"))));
+        assert!(findings.iter().any(|f| matches!(
+            &f.problem,
+            FsckProblem::UnparsableInfo { error: TrashInfoError::MissingHeader, path: Some(path), .. } if path.ends_with("files/unparsable")
+        )));
+        assert_eq!(names(&trash.path().join("files")), ["healthy", "orphan-dir", "unparsable"]);
+    }
+
+    #[test]
+    fn recreate_info() {
+        let trash = messy_trash_folder();
+        check(trash.path(), &FsckRepair::RecreateInfo { original_parent: "/recovered".into() });
+
+        assert_eq!(names(&trash.path().join("files")), ["healthy", "orphan-dir", "unparsable"]);
+        assert_eq!(
+            names(&trash.path().join("info")),
+            ["README", "healthy.trashinfo", "orphan-dir.trashinfo", "unparsable.trashinfo"]
+        );
+        for name in ["orphan-dir", "unparsable"] {
+            let info = fs::read(trash.path().join("info").join(format!("{name}.trashinfo"))).unwrap();
+            assert_eq!(TrashInfo::parse(&info).unwrap().path, Path::new("/recovered").join(name));
+        }
+
+        let mut findings = Vec::new();
+        fsck_trash_folder(trash.path(), &FsckRepair::None, &mut findings);
+        assert!(findings.is_empty(), "the trash folder is consistent after the repair: {findings:#?}");
+    }
+
+    #[test]
+    fn delete() {
+        let trash = messy_trash_folder();
+        check(trash.path(), &FsckRepair::Delete);
+
+        assert_eq!(names(&trash.path().join("files")), ["healthy"]);
+        assert_eq!(names(&trash.path().join("info")), ["README", "healthy.trashinfo"]);
+    }
+
+    #[test]
+    fn lost_and_found() {
+        let trash = messy_trash_folder();
+        fs::create_dir_all(trash.path().join(LOST_AND_FOUND_FOLDER_NAME).join("orphan-dir")).unwrap();
+        check(trash.path(), &FsckRepair::LostAndFound);
+
+        assert_eq!(names(&trash.path().join("files")), ["healthy"]);
+        assert_eq!(names(&trash.path().join("info")), ["README", "healthy.trashinfo"]);
+        assert_eq!(
+            names(&trash.path().join(LOST_AND_FOUND_FOLDER_NAME)),
+            ["orphan-dir", "orphan-dir.2", "unparsable", "unparsable.trashinfo"],
+            "existing entries in lost+found are not replaced"
+        );
+    }
+
+    #[test]
+    fn recent_dangling_info_is_kept() {
+        let trash = tempfile::tempdir().unwrap();
+        let info = trash.path().join("info");
+        fs::create_dir_all(&info).unwrap();
+        // Like an item that is being put into the trash, whose info file was just written.
+        fs::write(info.join("incoming.trashinfo"), "[Trash Info]\nPath=/tmp/incoming\n").unwrap();
+
+        let mut findings = Vec::new();
+        fsck_trash_folder(trash.path(), &FsckRepair::Delete, &mut findings);
+        assert!(findings.is_empty(), "{findings:#?}");
+        assert_eq!(names(&info), ["incoming.trashinfo"]);
+    }
+
+    #[test]
+    fn unreadable_info_keeps_items() {
+        let trash = messy_trash_folder();
+        fs::write(trash.path().join("files/victim"), "").unwrap();
+        // Reading a directory fails, just like reading an info file without permission.
+        fs::create_dir(trash.path().join("info/victim.trashinfo")).unwrap();
+
+        let mut findings = Vec::new();
+        fsck_trash_folder(trash.path(), &FsckRepair::Delete, &mut findings);
+        assert!(findings
+            .iter()
+            .any(|f| matches!(&f.problem, FsckProblem::Unreadable { path, .. } if path.ends_with("victim.trashinfo"))
+                && f.repair.is_none()));
+        assert!(!findings
+            .iter()
+            .any(|f| matches!(&f.problem, FsckProblem::Orphan { path } if path.ends_with("files/victim"))));
+        assert!(
+            findings.iter().filter(|f| matches!(f.problem, FsckProblem::Orphan { .. })).all(|f| f.repair.is_none()),
+            "orphans aren't repaired while an info file can't be read: {findings:#?}"
+        );
+        assert_eq!(names(&trash.path().join("files")), ["healthy", "orphan-dir", "victim"]);
+    }
+
+    #[test]
+    fn unreadable_files_keep_info() {
+        let trash = messy_trash_folder();
+        let mut findings = Vec::new();
+        let mut entries = read_trash_folder(&trash.path().join("files"), &trash.path().join("info"), &mut findings);
+        // Like a failure to read `$trash/files`.
+        entries.items.clear();
+        entries.all_items_read = false;
+        check_trash_folder(trash.path(), entries, &FsckRepair::Delete, &mut findings);
+
+        assert!(findings
+            .iter()
+            .any(|f| matches!(&f.problem, FsckProblem::DanglingInfo { info_path } if info_path.ends_with("dangling.trashinfo"))));
+        assert!(findings
+            .iter()
+            .filter(|f| matches!(f.problem, FsckProblem::DanglingInfo { .. } | FsckProblem::UnparsableInfo { .. }))
+            .all(|f| f.repair.is_none()));
+        assert_eq!(
+            names(&trash.path().join("info")),
+            ["README", "dangling.trashinfo", "healthy.trashinfo", "unparsable.trashinfo"]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! A journal of the items that were put into the trash, so that they can be restored later even by another process,
+//! like after an application restarted.
+//!
+//! The journal has one line per item, `batch time time_deleted id original_path`, where `batch` identifies the
+//! call that put the item into the trash, `time` is when that happened in seconds since the UNIX epoch,
+//! and `id` and `original_path` are percent-encoded.
+
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    io::{ErrorKind, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
+
+use super::{lock_file, FsError, TrashFolderLock};
+use crate::TrashItem;
+
+/// The name of the journal file within the 'home trash', see [`JournalLocation::HomeTrash`].
+pub const JOURNAL_FILE_NAME: &str = "trash-rs.journal";
+
+/// Where the items that are put into the trash are recorded, see
+/// [`TrashContextExtFreedesktop::set_journal`](super::TrashContextExtFreedesktop::set_journal).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalLocation {
+    /// A file named [`JOURNAL_FILE_NAME`] in the 'home trash', which is shared by all applications that use it.
+    HomeTrash,
+    /// A file chosen by the application. A lock file with `.lock` appended to its name is created next to it.
+    Path(PathBuf),
+}
+
+/// The items that were put into the trash by a single call, as recorded in the journal.
+#[derive(Debug, Clone)]
+pub struct JournalBatch {
+    /// Identifies the batch within its journal. Later batches have greater ids, and ids aren't reused.
+    pub id: u64,
+    /// The number of non-leap seconds elapsed between the UNIX Epoch and the moment the batch was recorded.
+    pub time: i64,
+    pub items: Vec<TrashItem>,
+}
+
+/// A single line of the journal.
+struct JournalEntry {
+    batch: u64,
+    time: i64,
+    item: TrashItem,
+}
+
+fn parse(content: &[u8]) -> Vec<JournalEntry> {
+    content
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let mut fields = line.split(|b| *b == b' ');
+            let mut number = || std::str::from_utf8(fields.next()?).ok()?.parse::<i64>().ok();
+            let (batch, time, time_deleted) = (u64::try_from(number()?).ok()?, number()?, number()?);
+            let mut path =
+                || Some(PathBuf::from(OsString::from_vec(urlencoding::decode_binary(fields.next()?).into())));
+            let (id, original_path) = (path()?, path()?);
+            Some(JournalEntry {
+                batch,
+                time,
+                item: TrashItem {
+                    id: id.into(),
+                    name: original_path.file_name()?.to_owned(),
+                    original_parent: original_path.parent()?.to_owned(),
+                    time_deleted,
+                },
+            })
+        })
+        .collect()
+}
+
+fn serialize(entry: &JournalEntry) -> Vec<u8> {
+    let JournalEntry { batch, time, item } = entry;
+    let id = urlencoding::encode_binary(item.id.as_bytes());
+    let original_path = urlencoding::encode_binary(item.original_path().as_os_str().as_bytes()).into_owned();
+    format!("{batch} {time} {} {id} {original_path}\n", item.time_deleted).into_bytes()
+}
+
+fn read(path: &Path) -> Result<Vec<JournalEntry>, FsError> {
+    match fs::read(path) {
+        Ok(content) => Ok(parse(&content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err((path.to_owned(), e)),
+    }
+}
+
+/// Returns all batches of the journal at `path`, oldest first. A missing journal is treated like an empty one.
+pub(super) fn read_batches(path: &Path) -> Result<Vec<JournalBatch>, FsError> {
+    let mut batches: Vec<JournalBatch> = Vec::new();
+    for JournalEntry { batch, time, item } in read(path)? {
+        match batches.iter_mut().find(|existing| existing.id == batch) {
+            Some(existing) => existing.items.push(item),
+            None => batches.push(JournalBatch { id: batch, time, items: vec![item] }),
+        }
+    }
+    batches.sort_by_key(|batch| batch.id);
+    Ok(batches)
+}
+
+/// Locks the journal at `path` by its lock file, which is created along with its folder if needed.
+fn lock(path: &Path) -> Result<TrashFolderLock, FsError> {
+    let folder = path.parent().unwrap_or(Path::new("/"));
+    fs::create_dir_all(folder).map_err(|e| (folder.to_owned(), e))?;
+    let mut lock_name = path.file_name().unwrap_or_default().to_owned();
+    lock_name.push(".lock");
+    let lock_path = folder.join(lock_name);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| (lock_path.clone(), e))?;
+    lock_file(file, &lock_path)
+}
+
+/// Appends `items` as a new batch to the journal at `path`, and returns the id of the batch. Older entries with the
+/// same ids are removed at the same time, as those items left the trash before their ids were reused.
+pub(super) fn record(path: &Path, items: &[TrashItem]) -> Result<u64, FsError> {
+    let _lock = lock(path)?;
+    let elapsed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    let time = elapsed.as_secs() as i64;
+    let mut entries = read(path)?;
+    // Ids are based on the time so that they aren't reused once batches are removed, even by other processes.
+    let latest = entries.iter().map(|entry| entry.batch).max().unwrap_or(0);
+    let batch = (elapsed.as_nanos() as u64).max(latest + 1);
+    let new_entries = items.iter().map(|item| JournalEntry { batch, time, item: item.clone() });
+    let len = entries.len();
+    let ids: HashSet<_> = items.iter().map(|item| &item.id).collect();
+    entries.retain(|entry| !ids.contains(&entry.item.id));
+    if entries.len() < len {
+        entries.extend(new_entries);
+        write(path, &entries)?;
+        return Ok(batch);
+    }
+    let content: Vec<u8> = new_entries.flat_map(|entry| serialize(&entry)).collect();
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(&content))
+        .map_err(|e| (path.to_owned(), e))?;
+    Ok(batch)
+}
+
+/// Removes the entries for which `keep` returns `false` from the journal at `path`.
+pub(super) fn retain(path: &Path, mut keep: impl FnMut(&TrashItem) -> bool) -> Result<(), FsError> {
+    let _lock = lock(path)?;
+    let entries = read(path)?;
+    let len = entries.len();
+    let entries: Vec<_> = entries.into_iter().filter(|entry| keep(&entry.item)).collect();
+    if entries.len() == len {
+        return Ok(());
+    }
+    write(path, &entries)
+}
+
+/// Replaces the journal at `path` with `entries`, which must happen while the journal is locked.
+fn write(path: &Path, entries: &[JournalEntry]) -> Result<(), FsError> {
+    let folder = path.parent().unwrap_or(Path::new("/"));
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(format!(".{}", std::process::id()));
+    let tmp_path = folder.join(tmp_name);
+    fs::write(&tmp_path, entries.iter().flat_map(serialize).collect::<Vec<u8>>()).map_err(|e| (tmp_path.clone(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        (path.to_owned(), e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs::File, os::unix::ffi::OsStringExt, path::Path};
+
+    use super::{read_batches, record, retain};
+    use crate::{
+        freedesktop::{tests::isolated_context, JournalLocation, TrashContextExtFreedesktop, JOURNAL_FILE_NAME},
+        TrashContext, TrashItem,
+    };
+
+    fn item(id: &str, original_parent: &Path, name: OsString) -> TrashItem {
+        TrashItem { id: id.into(), name, original_parent: original_parent.to_owned(), time_deleted: 1_700_000_000 }
+    }
+
+    #[test]
+    fn record_and_retain() {
+        let tmp = tempfile::tempdir().unwrap();
+        let journal = tmp.path().join("journal");
+        let odd_name = OsString::from_vec(b"with space, 100% and \xa8\n".to_vec());
+        let first = [item("/trash/info/a.trashinfo", Path::new("/home/a"), "a".into())];
+        let second = [
+            item("/trash/info/b.trashinfo", Path::new("/home/b"), odd_name.clone()),
+            item("/trash/info/c c.trashinfo", Path::new("/"), "c".into()),
+        ];
+        let first_id = record(&journal, &first).unwrap();
+        let second_id = record(&journal, &second).unwrap();
+        assert!(tmp.path().join("journal.lock").is_file(), "the journal is locked by its own lock file");
+        assert!(second_id > first_id);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, b"garbage\n"))
+            .unwrap();
+
+        let batches = read_batches(&journal).unwrap();
+        assert_eq!(batches.iter().map(|batch| batch.id).collect::<Vec<_>>(), [first_id, second_id]);
+        assert_eq!(batches[1].items, second, "ids are equal");
+        assert_eq!(batches[1].items[0].name, odd_name);
+        assert_eq!(batches[1].items[1].original_path(), Path::new("/c"));
+        assert_eq!(batches[1].items[1].time_deleted, 1_700_000_000);
+
+        retain(&journal, |item| item.name != odd_name).unwrap();
+        let batches = read_batches(&journal).unwrap();
+        assert_eq!(batches[0].items, first);
+        assert_eq!(batches[1].items, second[1..]);
+        retain(&journal, |item| item.name == "a").unwrap();
+        assert_eq!(read_batches(&journal).unwrap().len(), 1);
+
+        let third_id = record(&journal, &first).unwrap();
+        assert!(third_id > second_id, "ids aren't reused");
+        let batches = read_batches(&journal).unwrap();
+        assert_eq!(
+            batches.iter().map(|batch| batch.id).collect::<Vec<_>>(),
+            [third_id],
+            "the entries of reused item ids were replaced"
+        );
+    }
+
+    #[test]
+    fn missing_journal_is_empty() {
+        assert!(read_batches(Path::new("/does/not/exist")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn journal_undo() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        assert!(ctx.journal_batches().is_err(), "there's no journal by default");
+        ctx.set_journal(Some(JournalLocation::HomeTrash));
+        let paths = [root.join("a"), root.join("b"), root.join("c")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+
+        let first = ctx.delete_all_with_items(&paths[..2]).unwrap();
+        let second = ctx.delete_all_with_items(&paths[2..]).unwrap();
+        assert!(root.join("Trash").join(JOURNAL_FILE_NAME).is_file());
+        let batches = ctx.journal_batches().unwrap();
+        assert_eq!(batches.iter().map(|batch| &batch.items).collect::<Vec<_>>(), [&first, &second]);
+
+        let undone = ctx.undo_last().unwrap().unwrap();
+        assert_eq!(undone.items, second);
+        assert!(paths[2].is_file());
+        assert!(!paths[0].exists());
+
+        // The journal is shared with other contexts, like after a restart, and skips items that were purged.
+        let mut other = TrashContext::default();
+        other.set_home_trash(Some(root.join("Trash")));
+        other.set_topdirs(Some(Vec::new()));
+        other.set_journal(Some(JournalLocation::HomeTrash));
+        other.purge_all(&first[1..]).unwrap();
+        assert_eq!(other.undo(batches[0].id).unwrap().id, batches[0].id);
+        assert!(paths[0].is_file());
+        assert!(!paths[1].exists());
+
+        assert!(ctx.journal_batches().unwrap().is_empty());
+        assert!(ctx.undo_last().unwrap().is_none());
+        assert!(ctx.undo(batches[0].id).is_err());
+    }
+
+    #[test]
+    fn undo_skips_reused_ids() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        ctx.set_journal(Some(JournalLocation::HomeTrash));
+        for dir in ["first", "second"] {
+            std::fs::create_dir(root.join(dir)).unwrap();
+            File::create_new(root.join(dir).join("file")).unwrap();
+        }
+        let first = ctx.delete_all_with_items([root.join("first/file")]).unwrap();
+        ctx.purge_all(&first).unwrap();
+
+        // Another application puts an item with the same name into the trash, which gets the same id.
+        let mut other = TrashContext::default();
+        other.set_home_trash(Some(root.join("Trash")));
+        other.set_topdirs(Some(Vec::new()));
+        let second = other.delete_all_with_items([root.join("second/file")]).unwrap();
+        assert_eq!(second[0].id, first[0].id);
+
+        ctx.undo_last().unwrap().unwrap();
+        assert!(!root.join("first/file").exists());
+        assert!(!root.join("second/file").exists(), "the newer item isn't restored in place of the older one");
+        assert_eq!(ctx.list().unwrap(), second);
+        assert!(ctx.journal_batches().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,2591 @@
+//! This implementation will manage the trash according to the Freedesktop Trash specification,
+//! version 1.0 found at <https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html>
+//!
+//! Most -if not all- Linux based desktop operating systems implement the Trash according to this specification.
+//! In other words: I looked, but I could not find any Linux based desktop OS that used anything else than the
+//! Freedesktop Trash specification.
+//!
+
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        io::AsRawFd,
+    },
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use log::{debug, warn};
+
+use crate::{
+    Error, ItemError, ItemErrorKind, TrashContext, TrashItem, TrashItemDiskUsage, TrashItemMetadata, TrashItemSize,
+};
+
+mod browse;
+mod copy;
+mod empty;
+mod fsck;
+mod journal;
+#[cfg(target_os = "linux")]
+mod mount_info;
+mod progress;
+mod quota;
+mod restore;
+mod retention;
+mod trash_info;
+pub(crate) use browse::{open, read_trashed_dir, walk_trashed_dir};
+pub use browse::{TrashedContent, TrashedEntry};
+pub(crate) use empty::{empty, empty_folder};
+pub(crate) use fsck::fsck;
+pub use fsck::{FsckFinding, FsckProblem, FsckRepair, InvalidTrashFolder, LOST_AND_FOUND_FOLDER_NAME};
+pub use journal::{JournalBatch, JournalLocation, JOURNAL_FILE_NAME};
+use progress::{is_cancelled_error, ItemProgress, SharedObserver};
+pub use progress::{CancellationToken, Progress, ProgressObserver};
+pub(crate) use quota::enforce_quota;
+pub use quota::{Quota, QuotaScope};
+pub(crate) use restore::{restore_all_with_options, restore_sub_path};
+pub use restore::{RestoreCollisionStrategy, RestoreOptions, RestoreOutcome};
+pub(crate) use retention::purge_older_than;
+pub use retention::{RetentionOptions, UnknownDeletionDate};
+pub use trash_info::{DeletionDate, TrashInfo, TrashInfoError};
+
+type FsError = (PathBuf, std::io::Error);
+
+#[derive(Clone, Debug)]
+pub struct PlatformTrashContext {
+    relative_topdir_paths: bool,
+    home_trash: Option<PathBuf>,
+    topdirs: Option<Vec<PathBuf>>,
+    progress_observer: Option<SharedObserver>,
+    cancellation_token: Option<CancellationToken>,
+    journal: Option<JournalLocation>,
+    quota: Option<Quota>,
+}
+impl PlatformTrashContext {
+    pub const fn new() -> Self {
+        Self {
+            relative_topdir_paths: false,
+            home_trash: None,
+            topdirs: None,
+            progress_observer: None,
+            cancellation_token: None,
+            journal: None,
+            quota: None,
+        }
+    }
+
+    fn item_progress<'a>(&'a self, item: &'a Path) -> ItemProgress<'a> {
+        ItemProgress::new(
+            self.progress_observer.as_ref().map(|observer| &*observer.0),
+            self.cancellation_token.as_ref(),
+            item,
+        )
+    }
+
+    fn home_trash(&self) -> Result<PathBuf, Error> {
+        match &self.home_trash {
+            Some(home_trash) => Ok(home_trash.clone()),
+            None => home_trash(),
+        }
+    }
+
+    fn journal_path(&self) -> Result<Option<PathBuf>, Error> {
+        match &self.journal {
+            Some(JournalLocation::HomeTrash) => Ok(Some(self.home_trash()?.join(JOURNAL_FILE_NAME))),
+            Some(JournalLocation::Path(path)) => Ok(Some(path.clone())),
+            None => Ok(None),
+        }
+    }
+
+    fn sorted_mount_points(&self) -> Result<Vec<MountPoint>, Error> {
+        match &self.topdirs {
+            Some(topdirs) => Ok(sort_mount_points(
+                topdirs
+                    .iter()
+                    .map(|topdir| MountPoint {
+                        mnt_dir: topdir.clone(),
+                        _mnt_type: String::new(),
+                        _mnt_fsname: String::new(),
+                    })
+                    .collect(),
+            )),
+            None => get_sorted_mount_points(),
+        }
+    }
+}
+impl Default for PlatformTrashContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+pub trait TrashContextExtFreedesktop {
+    /// Sets whether items that are put into the trash folder of a mount point, like `$topdir/.Trash-$uid`,
+    /// record their original location relative to `$topdir`.
+    ///
+    /// This keeps them restorable after the mount point moved, like when a USB drive is mounted
+    /// elsewhere. The 'home trash' always records absolute paths.
+    ///
+    /// This is disabled by default, so that absolute paths are recorded like in earlier versions of this crate.
+    /// GNOME records relative paths.
+    fn set_relative_topdir_paths(&mut self, relative: bool);
+    fn relative_topdir_paths(&self) -> bool;
+
+    /// Sets the path of the 'home trash', like `~/.local/share/Trash`, instead of deriving it from the
+    /// `XDG_DATA_HOME` or `HOME` environment variables. `None` restores the default.
+    ///
+    /// The folder is created when the first item is put into it.
+    fn set_home_trash(&mut self, home_trash: Option<PathBuf>);
+    fn home_trash(&self) -> Option<&Path>;
+
+    /// Sets the topdirs, which are the directories where file systems are mounted, instead of reading them
+    /// from the system's mount table. `None` restores the default.
+    ///
+    /// Items outside of all topdirs, as well as items that share their topdir with the 'home trash', are
+    /// put into the 'home trash'. Other items are put into the trash folder of their topdir, like
+    /// `$topdir/.Trash-$uid`, and only these trash folders are searched besides the 'home trash'.
+    /// Together with [`set_home_trash`](Self::set_home_trash) this isolates the context from the system's
+    /// trash, which is useful for tests and sandboxes.
+    fn set_topdirs(&mut self, topdirs: Option<Vec<PathBuf>>);
+    fn topdirs(&self) -> Option<&[PathBuf]>;
+
+    /// Sets the observer that receives the [`Progress`] of every item that's deleted, purged or restored
+    /// with this context. `None`, the default, disables progress reporting.
+    fn set_progress_observer(&mut self, observer: Option<Arc<dyn ProgressObserver>>);
+    fn progress_observer(&self) -> Option<&Arc<dyn ProgressObserver>>;
+
+    /// Sets the token to cancel operations of this context with, which fail with [`Error::Cancelled`]
+    /// once it's cancelled. `None`, the default, makes operations run to completion.
+    ///
+    /// The token is checked before every item, and between the files of items that are copied across
+    /// file systems or purged. An item whose copy was cancelled is left where it was, and an item whose purge
+    /// was cancelled is left partially in the trash.
+    fn set_cancellation_token(&mut self, token: Option<CancellationToken>);
+    fn cancellation_token(&self) -> Option<&CancellationToken>;
+
+    /// Sets where every call of `delete_all` records the items it put into the trash, as a [`JournalBatch`].
+    /// `None`, the default, disables the journal.
+    ///
+    /// The batches can be undone with [`TrashContext::undo`] and [`TrashContext::undo_last`], also by
+    /// another process or after a restart, as long as it uses the same journal.
+    fn set_journal(&mut self, journal: Option<JournalLocation>);
+    fn journal(&self) -> Option<&JournalLocation>;
+
+    /// Sets a [`Quota`] that's enforced after every call of `delete_all`, by purging the oldest items like
+    /// [`TrashContext::enforce_quota`]. The items that were just put into the trash are never purged, and
+    /// failing to enforce the quota is logged instead of failing `delete_all`. `None`, the default, disables this.
+    fn set_quota(&mut self, quota: Option<Quota>);
+    fn quota(&self) -> Option<&Quota>;
+}
+impl TrashContextExtFreedesktop for TrashContext {
+    fn set_relative_topdir_paths(&mut self, relative: bool) {
+        self.platform_specific.relative_topdir_paths = relative;
+    }
+    fn relative_topdir_paths(&self) -> bool {
+        self.platform_specific.relative_topdir_paths
+    }
+    fn set_home_trash(&mut self, home_trash: Option<PathBuf>) {
+        self.platform_specific.home_trash = home_trash;
+    }
+    fn home_trash(&self) -> Option<&Path> {
+        self.platform_specific.home_trash.as_deref()
+    }
+    fn set_topdirs(&mut self, topdirs: Option<Vec<PathBuf>>) {
+        self.platform_specific.topdirs = topdirs;
+    }
+    fn topdirs(&self) -> Option<&[PathBuf]> {
+        self.platform_specific.topdirs.as_deref()
+    }
+    fn set_progress_observer(&mut self, observer: Option<Arc<dyn ProgressObserver>>) {
+        self.platform_specific.progress_observer = observer.map(SharedObserver);
+    }
+    fn progress_observer(&self) -> Option<&Arc<dyn ProgressObserver>> {
+        self.platform_specific.progress_observer.as_ref().map(|observer| &observer.0)
+    }
+    fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.platform_specific.cancellation_token = token;
+    }
+    fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.platform_specific.cancellation_token.as_ref()
+    }
+    fn set_journal(&mut self, journal: Option<JournalLocation>) {
+        self.platform_specific.journal = journal;
+    }
+    fn journal(&self) -> Option<&JournalLocation> {
+        self.platform_specific.journal.as_ref()
+    }
+    fn set_quota(&mut self, quota: Option<Quota>) {
+        self.platform_specific.quota = quota;
+    }
+    fn quota(&self) -> Option<&Quota> {
+        self.platform_specific.quota.as_ref()
+    }
+}
+impl TrashContext {
+    pub(crate) fn delete_all_canonicalized(&self, full_paths: Vec<PathBuf>) -> Result<(), Error> {
+        self.delete_all_canonicalized_with_items(full_paths).map(|_| ())
+    }
+
+    pub(crate) fn delete_all_canonicalized_with_items(
+        &self,
+        full_paths: Vec<PathBuf>,
+    ) -> Result<Vec<TrashItem>, Error> {
+        let journal_path = self.platform_specific.journal_path()?;
+        let mut items = Vec::with_capacity(full_paths.len());
+        let result = self.move_all_to_trash(full_paths, &mut items);
+        // Items that were put into the trash before a failure are recorded as well, so that they can be undone.
+        if let (Some(journal_path), false) = (journal_path, items.is_empty()) {
+            if let Err((p, e)) = journal::record(&journal_path, &items) {
+                warn!("Could not record the trashed items in the journal {:?}: {}", p, e);
+            }
+        }
+        if let (Some(quota), false) = (&self.platform_specific.quota, items.is_empty()) {
+            if let Err(e) = enforce_quota(self, quota, &items) {
+                warn!("Could not enforce the quota of the trash: {}", e);
+            }
+        }
+        match result {
+            Ok(()) => Ok(items),
+            // The items are in the trash, including the one whose source is partially left.
+            Err(Error::PartialSourceRemoval { path, copy, source, .. }) => {
+                Err(Error::PartialSourceRemoval { path, copy, source, items })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn move_all_to_trash(&self, full_paths: Vec<PathBuf>, items: &mut Vec<TrashItem>) -> Result<(), Error> {
+        // The home trash isn't canonicalized so that the ids of the items match the ones of `list`.
+        let home_trash = self.platform_specific.home_trash()?;
+        let sorted_mount_points = self.platform_specific.sorted_mount_points()?;
+        let home_trash_topdir = get_topdir_of_path(&canonicalize_path_or_parents(&home_trash)?, &sorted_mount_points);
+        debug!("The 'home trash' topdir is {:?}", home_trash_topdir);
+        let uid = unsafe { libc::getuid() };
+        for path in full_paths {
+            debug!("Deleting {:?}", path);
+            let mut progress = self.platform_specific.item_progress(&path);
+            progress.check_cancelled().map_err(|e| fs_error(&path, e))?;
+            let topdir = get_topdir_of_path(&path, &sorted_mount_points);
+            debug!("The topdir of this file is {:?}", topdir);
+            // Items outside of all topdirs, which only happens with explicit topdirs, go into the 'home trash' too.
+            let outside_topdirs = !sorted_mount_points.iter().any(|mp| mp.mnt_dir == topdir);
+            if topdir == home_trash_topdir || outside_topdirs {
+                debug!("The topdir was the 'home trash' topdir or no topdir at all, so moving to the home trash.");
+                // Note that the following function creates the trash folder
+                // and its required subfolders in case they don't exist.
+                let (item, partial_removal) =
+                    move_to_trash(&path, &home_trash, None, &mut progress).map_err(|(p, e)| fs_error(p, e))?;
+                items.push(item);
+                if let Some((p, e)) = partial_removal {
+                    return Err(fs_error(p, e));
+                }
+            } else {
+                let relative_to = self.platform_specific.relative_topdir_paths.then_some(topdir.as_path());
+                execute_on_mounted_trash_folders(uid, &topdir, true, true, |trash_path| {
+                    let (item, partial_removal) = move_to_trash(&path, trash_path, relative_to, &mut progress)?;
+                    items.push(item);
+                    partial_removal.map_or(Ok(()), Err)
+                })
+                .map_err(|(p, e)| fs_error(p, e))?;
+            }
+            progress.done();
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn list(ctx: &TrashContext) -> Result<Vec<TrashItem>, Error> {
+    let (items, errors) = list_with_errors(ctx)?;
+    for error in errors {
+        warn!("Skipping an entry of the trash: {}", error);
+    }
+    Ok(items)
+}
+
+pub(crate) fn list_with_errors(ctx: &TrashContext) -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+    let mut result = Vec::new();
+    let mut errors = Vec::new();
+    for item in list_iter(ctx)? {
+        match item {
+            Ok(item) => result.push(item),
+            Err(error) => errors.push(error),
+        }
+    }
+    Ok((result, errors))
+}
+
+pub(crate) fn list_iter(ctx: &TrashContext) -> Result<TrashItemIter, Error> {
+    let EvaluatedTrashFolders { trash_folders, home_error, sorted_mount_points } = eval_trash_folders(ctx)?;
+
+    if trash_folders.is_empty() {
+        warn!("No trash folder was found. The error when looking for the 'home trash' was: {:?}", home_error);
+    }
+    let folders: Vec<_> = trash_folders
+        .into_iter()
+        .map(|folder| {
+            let top_dir = get_topdir_of_path(&folder, &sorted_mount_points);
+            (folder, top_dir)
+        })
+        .collect();
+    Ok(TrashItemIter { folders: folders.into_iter(), current: None })
+}
+
+/// Yields the items of the trash one by one, as returned by [`list_iter`](crate::os_limited::list_iter).
+///
+/// The trash folders are read one after another, and each `.trashinfo` file is only read when its item is
+/// yielded.
+#[derive(Debug)]
+pub struct TrashItemIter {
+    /// The trash folders that weren't read yet, along with their topdirs.
+    folders: std::vec::IntoIter<(PathBuf, PathBuf)>,
+    /// The entries of the `info` folder that's being read, along with that folder and its topdir.
+    current: Option<(fs::ReadDir, PathBuf, PathBuf)>,
+}
+
+impl Iterator for TrashItemIter {
+    type Item = Result<TrashItem, ItemError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((read_dir, info_folder, top_dir)) = &mut self.current {
+                let info_entry = match read_dir.next() {
+                    Some(Ok(entry)) => entry,
+                    Some(Err(e)) => {
+                        return Some(Err(ItemError { path: info_folder.clone(), kind: ItemErrorKind::Io(e) }))
+                    }
+                    None => {
+                        self.current = None;
+                        continue;
+                    }
+                };
+                match list_item(&info_entry, top_dir) {
+                    Ok(Some(item)) => return Some(Ok(item)),
+                    Ok(None) => continue,
+                    Err(kind) => return Some(Err(ItemError { path: info_entry.path(), kind })),
+                }
+            }
+
+            // Read the info files of the next trash folder
+            let (folder, top_dir) = self.folders.next()?;
+            let info_folder = folder.join("info");
+            if !info_folder.is_dir() {
+                warn!("The path {:?} did not point to a directory, skipping this trash folder.", info_folder);
+                continue;
+            }
+            match std::fs::read_dir(&info_folder) {
+                Ok(read_dir) => self.current = Some((read_dir, info_folder, top_dir)),
+                // After all the earlier checks, it's still possible that the directory does not exist at this point (or is not readable)
+                // because another process may have deleted it or modified its access rights in the meantime.
+                // So let's report it and continue to the rest of the folders
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Some(Err(ItemError { path: info_folder, kind: ItemErrorKind::Io(e) })),
+            }
+        }
+    }
+}
+
+/// Turns an entry of a trash folder's `info` directory into a [`TrashItem`], or `None` if the
+/// entry should be skipped without reporting it.
+fn list_item(info_entry: &fs::DirEntry, top_dir: &Path) -> Result<Option<TrashItem>, ItemErrorKind> {
+    let info_path = info_entry.path();
+    // Entrty should really be an info file but better safe than sorry
+    let file_type = match info_entry.file_type() {
+        Ok(f_type) => f_type,
+        // Another thread or process may have removed that entry by now
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ItemErrorKind::Io(e)),
+    };
+    if !file_type.is_file() {
+        warn!("Found an item that's not a file, among the trash info files. This is unexpected. The path to the item is: '{:?}'", info_path);
+        return Ok(None);
+    }
+    let info_content = match fs::read(&info_path) {
+        Ok(content) => content,
+        // Another thread or process may have removed that entry by now
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ItemErrorKind::Io(e)),
+    };
+    let info = TrashInfo::parse(&info_content).map_err(ItemErrorKind::InvalidInfo)?;
+    let original_path = if info.path.is_relative() { top_dir.join(&info.path) } else { info.path };
+    let (Some(name), Some(original_parent)) = (original_path.file_name(), original_path.parent()) else {
+        return Err(ItemErrorKind::InvalidOriginalPath(original_path));
+    };
+    let time_deleted = info.deletion_date.and_then(DeletionDate::to_unix_time);
+    if time_deleted.is_none() {
+        warn!("Could not determine the deletion time of the trash item. (The `DeletionDate` field is probably missing from the info file.) The info file path is: '{:?}'", info_path);
+    }
+    Ok(Some(TrashItem {
+        id: info_path.into(),
+        name: name.to_owned(),
+        original_parent: original_parent.to_owned(),
+        time_deleted: time_deleted.unwrap_or(-1),
+    }))
+}
+
+pub(crate) fn is_empty(ctx: &TrashContext) -> Result<bool, Error> {
+    let EvaluatedTrashFolders { trash_folders, .. } = eval_trash_folders(ctx)?;
+    // Like `empty`, this considers everything in the `files` and `info` folders, including orphans and
+    // malformed info files, which `list_iter` doesn't yield as items.
+    for folder in trash_folders.iter().flat_map(|folder| [folder.join("files"), folder.join("info")]) {
+        match folder.read_dir() {
+            Ok(mut entries) => {
+                if let Some(Ok(_)) = entries.next() {
+                    return Ok(false);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                warn!("The trash folder {:?} could not be read. Error was {:?}", folder, e);
+            }
+        }
+    }
+    Ok(true)
+}
+
+pub(crate) fn trash_folders(ctx: &TrashContext) -> Result<HashSet<PathBuf>, Error> {
+    let EvaluatedTrashFolders { trash_folders, home_error, .. } = eval_trash_folders(ctx)?;
+
+    if trash_folders.is_empty() {
+        return match home_error {
+            Some(e) => Err(e),
+            None => Err(Error::Unknown {
+                description: "Could not find a valid 'home trash' nor valid trashes on other mount points".into(),
+            }),
+        };
+    }
+
+    Ok(trash_folders)
+}
+
+struct EvaluatedTrashFolders {
+    trash_folders: HashSet<PathBuf>,
+    home_error: Option<Error>,
+    sorted_mount_points: Vec<MountPoint>,
+}
+
+fn eval_trash_folders(ctx: &TrashContext) -> Result<EvaluatedTrashFolders, Error> {
+    let mut trash_folders = HashSet::new();
+    // Get home trash folder and add it to the set of trash folders.
+    // It may not exist and that's completely fine as long as there are other trash folders.
+    let home_error;
+    match ctx.platform_specific.home_trash() {
+        Ok(home_trash) => {
+            if !home_trash.is_dir() {
+                home_error = Some(Error::Unknown {
+                    description:
+                        "The 'home trash' either does not exist or is not a directory (or a link pointing to a dir)"
+                            .into(),
+                });
+            } else {
+                trash_folders.insert(home_trash);
+                home_error = None;
+            }
+        }
+        Err(e) => {
+            home_error = Some(e);
+        }
+    }
+
+    // Get all mount-points and attempt to find a trash folder in each adding them to the SET of
+    // trash folders when found one.
+    let uid = unsafe { libc::getuid() };
+    let sorted_mount_points = ctx.platform_specific.sorted_mount_points()?;
+    for mount in &sorted_mount_points {
+        execute_on_mounted_trash_folders(uid, &mount.mnt_dir, false, false, |trash_path| {
+            trash_folders.insert(trash_path);
+            Ok(())
+        })
+        .map_err(|(p, e)| fs_error(p, e))?;
+    }
+
+    Ok(EvaluatedTrashFolders { trash_folders, home_error, sorted_mount_points })
+}
+pub(crate) fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+    // When purging an item the "in-trash" filename must be parsed from the trashinfo filename
+    // which is the filename in the `id` field.
+    let info_file = &item.id;
+
+    let file = restorable_file_in_trash_from_info_file(info_file);
+    ensure_virtually_exists(&file)?;
+    let metadata = fs::symlink_metadata(&file).map_err(|e| fs_error(&file, e))?;
+    let is_dir = metadata.is_dir();
+    let size = if is_dir {
+        TrashItemSize::Entries(fs::read_dir(&file).map_err(|e| fs_error(&file, e))?.count())
+    } else {
+        TrashItemSize::Bytes(metadata.len())
+    };
+    // Directories aren't walked here, as that can take long, so their size is only known if it's cached.
+    let disk_usage = if is_dir {
+        cached_directory_size(&file, Path::new(info_file)).map(|bytes| TrashItemDiskUsage { bytes, files: None })
+    } else {
+        Some(disk_usage(&file).map_err(|e| fs_error(&file, e))?)
+    };
+    Ok(TrashItemMetadata { size, disk_usage })
+}
+
+/// The path points to:
+/// - existing file | directory | symlink => Ok(true)
+/// - broken symlink => Ok(true)
+/// - nothing => Ok(false)
+/// - I/O Error => Err(Io)
+#[inline]
+fn virtually_exists(path: &Path) -> std::io::Result<bool> {
+    Ok(path.try_exists()? || path.is_symlink())
+}
+
+/// Fails if `path`, an item within `$trash/files`, doesn't exist, which happens if its info file is dangling.
+fn ensure_virtually_exists(path: &Path) -> Result<(), Error> {
+    if virtually_exists(path).map_err(|e| fs_error(path, e))? {
+        Ok(())
+    } else {
+        Err(fs_error(path, std::io::Error::new(ErrorKind::NotFound, "the trash item has no file in the trash")))
+    }
+}
+
+pub(crate) fn purge_all<I>(ctx: &TrashContext, items: I) -> Result<(), Error>
+where
+    I: IntoIterator,
+    <I as IntoIterator>::Item: Borrow<TrashItem>,
+{
+    for item in items.into_iter() {
+        let original_path = item.borrow().original_path();
+        let mut progress = ctx.platform_specific.item_progress(&original_path);
+        progress.check_cancelled().map_err(|e| fs_error(&original_path, e))?;
+        // When purging an item the "in-trash" filename must be parsed from the trashinfo filename
+        // which is the filename in the `id` field.
+        let info_file = &item.borrow().id;
+
+        // A bunch of unwraps here. This is fine because if any of these fail that means
+        // that either there's a bug in this code or the target system didn't follow
+        // the specification.
+        let file = restorable_file_in_trash_from_info_file(info_file);
+        if file.is_dir() {
+            let removal = remove_dir_all_with_progress(&file, &mut progress);
+            // Even a partially removed directory has a different size.
+            remove_from_directory_sizes(&file);
+            removal.map_err(|(p, e)| fs_error(p, e))?;
+        } else {
+            std::fs::remove_file(&file).map_err(|e| fs_error(&file, e))?;
+        }
+        std::fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))?;
+        progress.done();
+    }
+
+    Ok(())
+}
+
+/// Like `std::fs::remove_dir_all`, but reports every removed entry to `progress`, and checks for cancellation
+/// before removing the next one.
+fn remove_dir_all_with_progress(path: &Path, progress: &mut ItemProgress<'_>) -> Result<(), FsError> {
+    for entry in std::fs::read_dir(path).map_err(|e| (path.to_owned(), e))? {
+        let entry_path = entry.map_err(|e| (path.to_owned(), e))?.path();
+        progress.check_cancelled().map_err(|e| (entry_path.clone(), e))?;
+        let metadata = entry_path.symlink_metadata().map_err(|e| (entry_path.clone(), e))?;
+        if metadata.is_dir() {
+            remove_dir_all_with_progress(&entry_path, progress)?;
+        } else {
+            std::fs::remove_file(&entry_path).map_err(|e| (entry_path.clone(), e))?;
+            progress.entry_done(metadata.len());
+        }
+    }
+    std::fs::remove_dir(path).map_err(|e| (path.to_owned(), e))?;
+    progress.entry_done(0);
+    Ok(())
+}
+
+fn restorable_file_in_trash_from_info_file(info_file: impl AsRef<std::ffi::OsStr>) -> PathBuf {
+    let info_file = info_file.as_ref();
+    let trash_folder = Path::new(info_file).parent().unwrap().parent().unwrap();
+    let name_in_trash = Path::new(info_file).file_stem().unwrap();
+    trash_folder.join("files").join(name_in_trash)
+}
+
+/// Returns the path and metadata of the entry at `sub_path` within `file`, a trashed item in `$trash/files`.
+/// An empty `sub_path` is the item itself.
+///
+/// Fails unless `sub_path` is relative, doesn't contain `..`, and leads only through directories of the item,
+/// as opposed to symbolic links out of it.
+fn entry_of_trashed_item(file: &Path, sub_path: &Path) -> Result<(PathBuf, fs::Metadata), Error> {
+    if sub_path.components().any(|component| !matches!(component, Component::Normal(_))) {
+        let e = std::io::Error::new(ErrorKind::InvalidInput, "the sub-path must be relative and must not contain `..`");
+        return Err(fs_error(sub_path, e));
+    }
+    let mut path = file.to_owned();
+    let mut metadata = path.symlink_metadata().map_err(|e| fs_error(&path, e))?;
+    for component in sub_path.components() {
+        if !metadata.is_dir() {
+            return Err(fs_error(&path, std::io::Error::new(ErrorKind::NotADirectory, "not a directory of the item")));
+        }
+        path.push(component);
+        metadata = path.symlink_metadata().map_err(|e| fs_error(&path, e))?;
+    }
+    Ok((path, metadata))
+}
+
+pub(crate) fn restore_all<I>(ctx: &TrashContext, items: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = TrashItem>,
+{
+    restore_all_to(
+        ctx,
+        items.into_iter().map(|item| {
+            let original_path = item.original_path();
+            (item, original_path)
+        }),
+    )
+}
+
+pub(crate) fn restore_all_to<I>(ctx: &TrashContext, items: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = (TrashItem, PathBuf)>,
+{
+    let mut iter = items.into_iter();
+    while let Some((item, dest)) = iter.next() {
+        if !restore_item_to(ctx, &item, &dest)? {
+            let remaining: Vec<_> = std::iter::once(item).chain(iter.map(|(item, _)| item)).collect();
+            return Err(Error::RestoreCollision { path: dest, remaining_items: remaining });
+        }
+    }
+    Ok(())
+}
+
+/// Moves the file of `item` out of the trash to `dest` and deletes its info file.
+///
+/// Returns `Ok(false)` without changing anything if something already exists at `dest`.
+fn restore_item_to(ctx: &TrashContext, item: &TrashItem, dest: &Path) -> Result<bool, Error> {
+    let original_path = item.original_path();
+    let mut progress = ctx.platform_specific.item_progress(&original_path);
+    progress.check_cancelled().map_err(|e| fs_error(&original_path, e))?;
+    // The "in-trash" filename must be parsed from the trashinfo filename
+    // which is the filename in the `id` field.
+    let info_file = &item.id;
+
+    // A bunch of unwraps here. This is fine because if any of these fail that means
+    // that either there's a bug in this code or the target system didn't follow
+    // the specification.
+    let file = restorable_file_in_trash_from_info_file(info_file);
+    ensure_virtually_exists(&file)?;
+    let is_dir = file.symlink_metadata().is_ok_and(|m| m.is_dir());
+    // Make sure the parent exists so that creating the placeholder doesn't fail due to that.
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| fs_error(parent, e))?;
+    }
+    match move_items_no_replace(&file, dest, &mut progress) {
+        Ok(()) => {}
+        // The placeholder couldn't be created, so the destination is taken.
+        Err((p, e)) if e.kind() == ErrorKind::AlreadyExists && p == dest => return Ok(false),
+        // The item stays in the trash with what's left of it.
+        Err((p, e)) if is_partial_source_removal(&e) => {
+            remove_from_directory_sizes(&file);
+            return Err(fs_error(p, e));
+        }
+        Err((p, e)) => return Err(fs_error(p, e)),
+    }
+    if is_dir {
+        remove_from_directory_sizes(&file);
+    }
+    std::fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))?;
+    progress.done();
+    Ok(true)
+}
+
+fn journal_path_or_error(ctx: &TrashContext) -> Result<PathBuf, Error> {
+    ctx.platform_specific
+        .journal_path()?
+        .ok_or_else(|| Error::Unknown { description: "No journal was set for the trash context".into() })
+}
+
+pub(crate) fn journal_batches(ctx: &TrashContext) -> Result<Vec<JournalBatch>, Error> {
+    let journal_path = journal_path_or_error(ctx)?;
+    journal::read_batches(&journal_path).map_err(|(p, e)| fs_error(p, e))
+}
+
+pub(crate) fn undo(ctx: &TrashContext, batch_id: u64) -> Result<JournalBatch, Error> {
+    let journal_path = journal_path_or_error(ctx)?;
+    let batch = journal::read_batches(&journal_path)
+        .map_err(|(p, e)| fs_error(p, e))?
+        .into_iter()
+        .find(|batch| batch.id == batch_id)
+        .ok_or_else(|| Error::Unknown { description: format!("The journal has no batch with the id {batch_id}") })?;
+    undo_batch(ctx, &journal_path, batch)
+}
+
+pub(crate) fn undo_last(ctx: &TrashContext) -> Result<Option<JournalBatch>, Error> {
+    let journal_path = journal_path_or_error(ctx)?;
+    match journal::read_batches(&journal_path).map_err(|(p, e)| fs_error(p, e))?.pop() {
+        Some(batch) => undo_batch(ctx, &journal_path, batch).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Restores the items of `batch` that are still in the trash, and removes the entries of items that aren't anymore
+/// from the journal, even if restoring failed.
+fn undo_batch(ctx: &TrashContext, journal_path: &Path, batch: JournalBatch) -> Result<JournalBatch, Error> {
+    // Items that were purged or restored in the meantime are skipped.
+    let in_trash = batch.items.iter().filter(|item| is_in_trash(item)).cloned();
+    let result = ctx.restore_all(in_trash.collect::<Vec<_>>());
+    journal::retain(journal_path, is_in_trash).map_err(|(p, e)| fs_error(p, e))?;
+    result.map(|()| batch)
+}
+
+/// Whether `item`, as recorded in a journal, is still in the trash. Its id alone isn't enough, as the names of
+/// items in the trash are reused once they left it, so the original path and deletion date have to match too.
+fn is_in_trash(item: &TrashItem) -> bool {
+    let Some(info) = fs::read(&item.id).ok().and_then(|content| TrashInfo::parse(&content).ok()) else {
+        return false;
+    };
+    let original_path = item.original_path();
+    // A relative path is relative to the topdir, which may have moved since.
+    let same_path =
+        if info.path.is_relative() { original_path.ends_with(&info.path) } else { info.path == original_path };
+    same_path && info.deletion_date.and_then(DeletionDate::to_unix_time).unwrap_or(-1) == item.time_deleted
+}
+
+/// According to the specification (see at the top of the file) there are two kinds of
+/// trash-folders for a mounted drive or partition.
+/// 1, .Trash/uid
+/// 2, .Trash-uid
+///
+/// This function executes `op` providing it with a
+/// trash-folder path that's associated with the partition mounted at `topdir`.
+///
+fn execute_on_mounted_trash_folders<F: FnMut(PathBuf) -> Result<(), FsError>>(
+    uid: u32,
+    topdir: impl AsRef<Path>,
+    first_only: bool,
+    create_folder: bool,
+    mut op: F,
+) -> Result<(), FsError> {
+    // See if there's a ".Trash" directory at the mounted location
+    let topdir = topdir.as_ref();
+    let trash_path = topdir.join(".Trash");
+    if trash_path.is_dir() {
+        let validity = folder_validity(&trash_path)?;
+        if validity == TrashValidity::Valid {
+            let users_trash_path = trash_path.join(uid.to_string());
+            let mut should_execute = users_trash_path.is_dir();
+            if !should_execute && create_folder {
+                // The spec asks for the per-user folder to be created if it doesn't exist yet,
+                // falling back to ".Trash-$uid" only if that fails.
+                match std::fs::DirBuilder::new().mode(0o700).create(&users_trash_path) {
+                    Ok(()) => should_execute = true,
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                        should_execute = users_trash_path.is_dir();
+                    }
+                    Err(e) => {
+                        warn!("Failed to create the trash folder at '{:?}': {}", users_trash_path, e);
+                    }
+                }
+            }
+            if should_execute {
+                op(users_trash_path)?;
+                if first_only {
+                    return Ok(());
+                }
+            }
+        } else {
+            warn!("A Trash folder was found at '{:?}', but it's invalid because it's {:?}", trash_path, validity);
+        }
+    }
+    // See if there's a ".Trash-$UID" directory at the mounted location
+    let trash_path = topdir.join(format!(".Trash-{uid}"));
+    let should_execute;
+    if !trash_path.exists() || !trash_path.is_dir() {
+        if create_folder {
+            std::fs::create_dir(&trash_path).map_err(|e| (trash_path.to_owned(), e))?;
+            should_execute = true;
+        } else {
+            should_execute = false;
+        }
+    } else {
+        should_execute = true;
+    }
+    if should_execute {
+        op(trash_path)?;
+    }
+    Ok(())
+}
+
+/// Moves `src` into `trash_folder` and returns the item it became. If `relative_to` is set, the original
+/// location is recorded relative to it, which should be the `$topdir` of the trash folder.
+///
+/// If the item was copied into the trash but its source could only be partially removed, the item is returned
+/// along with that error.
+fn move_to_trash(
+    src: impl AsRef<Path>,
+    trash_folder: impl AsRef<Path>,
+    relative_to: Option<&Path>,
+    progress: &mut ItemProgress<'_>,
+) -> Result<(TrashItem, Option<FsError>), FsError> {
+    let src = src.as_ref();
+    let trash_folder = trash_folder.as_ref();
+    let files_folder = trash_folder.join("files");
+    let info_folder = trash_folder.join("info");
+
+    // Ensure the `files` and `info` folders exist
+    std::fs::create_dir_all(&files_folder).map_err(|e| (files_folder.to_owned(), e))?;
+    std::fs::create_dir_all(&info_folder).map_err(|e| (info_folder.to_owned(), e))?;
+
+    // This kind of validity must only apply ot administrator style trash folders
+    // See Trash directories, (1) at https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html
+    //assert_eq!(folder_validity(trash_folder)?, TrashValidity::Valid);
+
+    // When trashing a file one must make sure that every trashed item is uniquely named.
+    // However the `rename` function -that is used in *nix systems to move files- by default
+    // overwrites the destination. Therefore when multiple threads are removing items with identical
+    // names, an implementation might accidently overwrite an item that was just put into the trash
+    // if it's not careful enough.
+    //
+    // The strategy here is to use the `create_new` parameter of `OpenOptions` to
+    // try creating a placeholder file in the trash but don't do so if one with an identical name
+    // already exist. This newly created empty file can then be safely overwritten by the src file
+    // using the `rename` function.
+    let filename = src.file_name().unwrap();
+    let deletion_date = DeletionDate::now();
+    let mut appendage = 0usize;
+    loop {
+        appendage += 1;
+        let in_trash_name: Cow<'_, OsStr> = if appendage > 1 {
+            let mut trash_name = filename.to_owned();
+            trash_name.push(format!(".{appendage}"));
+            trash_name.into()
+        } else {
+            filename.into()
+        };
+        // Length of name + length of '.trashinfo'
+        let mut info_name = OsString::with_capacity(in_trash_name.len() + 10);
+        info_name.push(&in_trash_name);
+        info_name.push(".trashinfo");
+        let info_file_path = info_folder.join(&info_name);
+        let info_result = OpenOptions::new().create_new(true).write(true).open(&info_file_path);
+        match info_result {
+            Err(error) => {
+                if error.kind() == std::io::ErrorKind::AlreadyExists {
+                    continue;
+                } else {
+                    debug!("Failed to create the new file {:?}", info_file_path);
+                    return Err((info_file_path, error));
+                }
+            }
+            Ok(mut file) => {
+                debug!("Successfully created {:?}", info_file_path);
+                // Write the info file before actually moving anything
+                let path = relative_to
+                    .and_then(|topdir| src.strip_prefix(topdir).ok())
+                    .filter(|path| !path.as_os_str().is_empty())
+                    .unwrap_or(src);
+                let info = TrashInfo { path: path.to_owned(), deletion_date, unknown: Vec::new() };
+                file.write_all(info.serialize().as_bytes()).map_err(|e| (info_file_path.to_owned(), e))?;
+            }
+        }
+        let path = files_folder.join(&in_trash_name);
+        let partial_removal = match move_items_no_replace(src, &path, progress) {
+            Ok(()) => None,
+            Err((src, error)) if is_partial_source_removal(&error) => {
+                // The item is in the trash, so its info file is kept.
+                warn!("Moved {:?} to the trash at {:?}, but failed to remove it completely: {:?}", src, path, error);
+                Some((src, error))
+            }
+            Err((path, error)) => {
+                debug!("Failed moving item to the trash (this is usually OK). {:?}", error);
+                // Try to delete the info file
+                if let Err(info_err) = std::fs::remove_file(info_file_path) {
+                    warn!("Created the trash info file, then failed to move the item to the trash. So far it's OK, but then failed remove the initial info file. There's either a bug in this program or another faulty program is manupulating the Trash. The error was: {:?}", info_err);
+                }
+                if error.kind() == std::io::ErrorKind::AlreadyExists {
+                    continue;
+                } else {
+                    return Err((path, error));
+                }
+            }
+        };
+        // We did it!
+        if path.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+            match disk_usage(&path) {
+                Ok(usage) => add_to_directory_sizes(&path, &info_file_path, usage.bytes),
+                Err(e) => warn!("Failed to determine the size of {:?} for the directory size cache: {:?}", path, e),
+            }
+        }
+        let item = TrashItem {
+            id: info_file_path.into(),
+            name: filename.to_owned(),
+            original_parent: src.parent().unwrap_or(Path::new("/")).to_owned(),
+            time_deleted: deletion_date.and_then(DeletionDate::to_unix_time).unwrap_or(-1),
+        };
+        return Ok((item, partial_removal));
+    }
+}
+
+/// An error may mean that a collision was found.
+fn move_items_no_replace(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    progress: &mut ItemProgress<'_>,
+) -> Result<(), FsError> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+
+    try_creating_placeholders(src, dst)?;
+
+    // Try to rename first (fastest option for same filesystem)
+    let Err(e) = std::fs::rename(src, dst) else { return Ok(()) };
+
+    let needs_cross_device_copy = e.kind() == ErrorKind::CrossesDevices;
+    if !needs_cross_device_copy {
+        remove_placeholder(dst);
+        return Err((src.to_owned(), e));
+    }
+
+    debug!("Cross-device move detected, falling back to copy+delete for {:?}", src);
+
+    // Copy the file/directory along with its metadata, without leaving a partial copy behind
+    if let Err(err) = copy::copy_item(src, dst, progress) {
+        remove_placeholder(dst);
+        return Err(err);
+    }
+
+    // Remove the source. Its entries were already reported when they were copied.
+    let is_dir = src.symlink_metadata().is_ok_and(|metadata| metadata.is_dir());
+    let removal = if is_dir {
+        remove_dir_all_with_progress(src, &mut progress.unobserved()).map_err(|(_, e)| e)
+    } else {
+        std::fs::remove_file(src)
+    };
+    removal.map_err(|source| {
+        let kind = source.kind();
+        (src.to_owned(), std::io::Error::new(kind, PartialSourceRemoval { copy: dst.to_owned(), source }))
+    })
+}
+
+/// Removes what `move_items_no_replace` created at `dst` after it failed, which is the placeholder or
+/// a partial copy.
+fn remove_placeholder(dst: &Path) {
+    let removal = match dst.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(dst),
+        Ok(_) => std::fs::remove_file(dst),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = removal {
+        warn!("Failed to clean up {:?} after failing to move an item there: {:?}", dst, e);
+    }
+}
+
+/// Carried by the `std::io::Error` of a [`FsError`] if an item was copied by `move_items_no_replace`,
+/// but its source could only be partially removed. [`fs_error`] turns it into [`Error::PartialSourceRemoval`].
+#[derive(Debug)]
+struct PartialSourceRemoval {
+    copy: PathBuf,
+    source: std::io::Error,
+}
+
+impl std::fmt::Display for PartialSourceRemoval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the item was copied to {:?}, but could only be partially removed: {}", self.copy, self.source)
+    }
+}
+
+impl std::error::Error for PartialSourceRemoval {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Returns `true` if `error` is from `move_items_no_replace`, whose item was moved but also partially
+/// left at its source.
+fn is_partial_source_removal(error: &std::io::Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<PartialSourceRemoval>())
+}
+
+fn try_creating_placeholders(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), FsError> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let metadata = src.symlink_metadata().map_err(|e| (src.to_owned(), e))?;
+    if metadata.is_dir() {
+        // NOTE create_dir fails if the directory already exists
+        std::fs::create_dir(dst).map_err(|e| (dst.to_owned(), e))?;
+    } else {
+        // Symlink or file
+        OpenOptions::new().create_new(true).write(true).open(dst).map_err(|e| (dst.to_owned(), e))?;
+    }
+    Ok(())
+}
+
+/// The name of the directory size cache within a trash folder, see "Directory size cache" in the specification.
+const DIRECTORY_SIZES_FILE_NAME: &str = "directorysizes";
+
+/// A single line of the `$trash/directorysizes` cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirectorySize {
+    /// The disk usage of the directory and everything below it, in bytes.
+    size: u64,
+    /// The modification time of the directory's `.trashinfo` file, in seconds since the UNIX epoch.
+    /// An entry is only valid as long as this matches the info file.
+    mtime: i64,
+    /// The (decoded) name of the directory within `$trash/files`.
+    name: OsString,
+}
+
+/// Parses the content of a `directorysizes` file, skipping lines that don't follow the specification.
+fn parse_directory_sizes(content: &[u8]) -> Vec<DirectorySize> {
+    content
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, |b| *b == b' ');
+            let size = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+            let mtime = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+            let name = fields.next().filter(|name| !name.is_empty())?;
+            Some(DirectorySize { size, mtime, name: OsString::from_vec(urlencoding::decode_binary(name).into_owned()) })
+        })
+        .collect()
+}
+
+fn serialize_directory_sizes(entries: &[DirectorySize]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for entry in entries {
+        let name = urlencoding::encode_binary(entry.name.as_bytes());
+        content.extend_from_slice(format!("{} {} {}\n", entry.size, entry.mtime, name).as_bytes());
+    }
+    content
+}
+
+/// Reads the directory size cache of `trash_folder`. A missing cache is treated like an empty one.
+fn read_directory_sizes(trash_folder: &Path) -> Result<Vec<DirectorySize>, FsError> {
+    let path = trash_folder.join(DIRECTORY_SIZES_FILE_NAME);
+    match fs::read(&path) {
+        Ok(content) => Ok(parse_directory_sizes(&content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err((path, e)),
+    }
+}
+
+/// Applies `update` to the directory size cache of `trash_folder` while holding a lock on the trash folder.
+///
+/// `update` returns `true` if it changed the entries, in which case the cache is replaced atomically
+/// by writing a temporary file and renaming it, as the specification demands.
+fn update_directory_sizes(
+    trash_folder: &Path,
+    update: impl FnOnce(&mut Vec<DirectorySize>) -> bool,
+) -> Result<(), FsError> {
+    let _lock = lock_trash_folder(trash_folder)?;
+    let mut entries = read_directory_sizes(trash_folder)?;
+    if !update(&mut entries) {
+        return Ok(());
+    }
+    let path = trash_folder.join(DIRECTORY_SIZES_FILE_NAME);
+    let tmp_path = trash_folder.join(format!(".{DIRECTORY_SIZES_FILE_NAME}.{}", std::process::id()));
+    fs::write(&tmp_path, serialize_directory_sizes(&entries)).map_err(|e| (tmp_path.clone(), e))?;
+    fs::rename(&tmp_path, &path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        (path, e)
+    })
+}
+
+/// Returns the cached size of `file`, a directory within `$trash/files`, if the cache has an entry
+/// for it that is still valid for `info_file`.
+fn cached_directory_size(file: &Path, info_file: &Path) -> Option<u64> {
+    let trash_folder = file.parent()?.parent()?;
+    let name = file.file_name()?;
+    let mtime = fs::metadata(info_file).ok()?.mtime();
+    let entries = read_directory_sizes(trash_folder)
+        .map_err(|(path, e)| debug!("Could not read the directory size cache at {:?}: {:?}", path, e))
+        .ok()?;
+    entries.into_iter().find(|entry| entry.name == name && entry.mtime == mtime).map(|entry| entry.size)
+}
+
+/// Records `size` as the size of `file`, a directory within `$trash/files`.
+///
+/// The cache is an optimization only, so failing to update it is logged instead of failing the
+/// trash operation.
+fn add_to_directory_sizes(file: &Path, info_file: &Path, size: u64) {
+    let (Some(trash_folder), Some(name)) = (file.parent().and_then(Path::parent), file.file_name()) else {
+        return;
+    };
+    let result = fs::metadata(info_file).map_err(|e| (info_file.to_owned(), e)).and_then(|metadata| {
+        let mtime = metadata.mtime();
+        update_directory_sizes(trash_folder, |entries| {
+            entries.retain(|entry| entry.name != name);
+            entries.push(DirectorySize { size, mtime, name: name.to_owned() });
+            true
+        })
+    });
+    if let Err((path, e)) = result {
+        warn!("Failed to add {:?} to the directory size cache, error at {:?} was: {:?}", file, path, e);
+    }
+}
+
+/// Removes the entry of `file`, a directory that was purged from or restored out of `$trash/files`,
+/// from the directory size cache.
+fn remove_from_directory_sizes(file: &Path) {
+    let (Some(trash_folder), Some(name)) = (file.parent().and_then(Path::parent), file.file_name()) else {
+        return;
+    };
+    let result = update_directory_sizes(trash_folder, |entries| {
+        let len = entries.len();
+        entries.retain(|entry| entry.name != name);
+        entries.len() != len
+    });
+    if let Err((path, e)) = result {
+        warn!("Failed to remove {:?} from the directory size cache, error at {:?} was: {:?}", file, path, e);
+    }
+}
+
+/// The directory size cache of a trash folder, which is read once and extended by the sizes of the directories
+/// that were missing from it, so that measuring all items doesn't rewrite it once per directory.
+struct DirectorySizeCache {
+    entries: HashMap<OsString, DirectorySize>,
+    missing: Vec<DirectorySize>,
+}
+
+impl DirectorySizeCache {
+    fn read(trash_folder: &Path) -> Self {
+        let entries = read_directory_sizes(trash_folder)
+            .map_err(|(path, e)| debug!("Could not read the directory size cache at {:?}: {:?}", path, e))
+            .unwrap_or_default();
+        Self { entries: entries.into_iter().map(|entry| (entry.name.clone(), entry)).collect(), missing: Vec::new() }
+    }
+
+    /// Returns the space the item of `info_file` occupies on disk in bytes, and measures it if it's a directory
+    /// that isn't cached yet.
+    fn disk_usage(&mut self, info_file: &Path) -> std::io::Result<u64> {
+        let file = restorable_file_in_trash_from_info_file(info_file);
+        if !file.symlink_metadata()?.is_dir() {
+            return disk_usage(&file).map(|usage| usage.bytes);
+        }
+        let name = file.file_name().unwrap_or_default();
+        let mtime = fs::metadata(info_file)?.mtime();
+        match self.entries.get(name) {
+            Some(entry) if entry.mtime == mtime => Ok(entry.size),
+            _ => {
+                let size = disk_usage(&file)?.bytes;
+                self.missing.push(DirectorySize { size, mtime, name: name.to_owned() });
+                Ok(size)
+            }
+        }
+    }
+
+    /// Adds the sizes that were missing to the cache of `trash_folder`.
+    fn write(self, trash_folder: &Path) {
+        if self.missing.is_empty() {
+            return;
+        }
+        let names: HashSet<_> = self.missing.iter().map(|entry| entry.name.clone()).collect();
+        let result = update_directory_sizes(trash_folder, |entries| {
+            entries.retain(|entry| !names.contains(&entry.name));
+            entries.extend(self.missing);
+            true
+        });
+        if let Err((path, e)) = result {
+            warn!("Failed to update the directory size cache of {:?}, error at {:?} was: {:?}", trash_folder, path, e);
+        }
+    }
+}
+
+/// Holds an exclusive advisory lock on a trash folder, or on another file, for as long as it's alive.
+struct TrashFolderLock {
+    _folder: File,
+}
+
+fn lock_trash_folder(trash_folder: &Path) -> Result<TrashFolderLock, FsError> {
+    let folder = File::open(trash_folder).map_err(|e| (trash_folder.to_owned(), e))?;
+    lock_file(folder, trash_folder)
+}
+
+/// Waits for an exclusive lock on `file`, which was opened from `path`.
+fn lock_file(file: File, path: &Path) -> Result<TrashFolderLock, FsError> {
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(TrashFolderLock { _folder: file });
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != ErrorKind::Interrupted {
+            return Err((path.to_owned(), error));
+        }
+    }
+}
+
+/// Returns the disk space used by `path` and everything below it, in bytes, the way `du -B1` counts it,
+/// along with the number of non-directory entries.
+/// Symlinks are not followed and the space of hard-linked files is only counted once.
+fn disk_usage(path: &Path) -> std::io::Result<TrashItemDiskUsage> {
+    let mut seen_inodes = HashSet::new();
+    let mut pending = vec![path.to_owned()];
+    let (mut bytes, mut files) = (0, 0);
+    while let Some(path) = pending.pop() {
+        let metadata = path.symlink_metadata()?;
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+        } else {
+            files += 1;
+            if metadata.nlink() > 1 && !seen_inodes.insert((metadata.dev(), metadata.ino())) {
+                continue;
+            }
+        }
+        bytes += metadata.blocks() * 512;
+    }
+    Ok(TrashItemDiskUsage { bytes, files: Some(files) })
+}
+
+fn decode_uri_path(path: impl AsRef<Path>) -> PathBuf {
+    // Paths may be invalid Unicode on most Unixes so they should be treated as byte strings
+    // A higher level crate, such as `url`, can't be used directly since its API intakes valid Rust
+    // strings. Thus, the easiest way is to manually decode each segment of the path and recombine.
+    path.as_ref().iter().map(|part| OsString::from_vec(urlencoding::decode_binary(part.as_bytes()).to_vec())).collect()
+}
+
+fn encode_uri_path(path: impl AsRef<Path>) -> String {
+    // `iter()` cannot be used here because it yields '/' in certain situations, such as
+    // for root directories.
+    // Slashes would be encoded and thus mess up the path
+    let path: PathBuf = path
+        .as_ref()
+        .components()
+        .map(|component| {
+            // Only encode names and not '/', 'C:\', et cetera
+            if let Component::Normal(part) = component {
+                urlencoding::encode_binary(part.as_bytes()).to_string()
+            } else {
+                component.as_os_str().to_str().expect("Path components such as '/' are valid Unicode").to_owned()
+            }
+        })
+        .collect();
+
+    path.to_str().expect("URL encoded bytes is valid Unicode").to_owned()
+}
+
+#[derive(Eq, PartialEq, Debug)]
+enum TrashValidity {
+    Valid,
+    InvalidSymlink,
+    InvalidNotSticky,
+}
+
+fn folder_validity(path: impl AsRef<Path>) -> Result<TrashValidity, FsError> {
+    /// Mask for the sticky bit
+    /// Taken from: http://man7.org/linux/man-pages/man7/inode.7.html
+    const S_ISVTX: u32 = 0o1000;
+
+    let path = path.as_ref();
+    let metadata = path.symlink_metadata().map_err(|e| (path.to_owned(), e))?;
+    if metadata.file_type().is_symlink() {
+        return Ok(TrashValidity::InvalidSymlink);
+    }
+    let mode = metadata.permissions().mode();
+    let no_sticky_bit = (mode & S_ISVTX) == 0;
+    if no_sticky_bit {
+        return Ok(TrashValidity::InvalidNotSticky);
+    }
+    Ok(TrashValidity::Valid)
+}
+
+/// Corresponds to the definition of "home_trash" from
+/// https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html
+fn home_trash() -> Result<PathBuf, Error> {
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
+        if !data_home.is_empty() {
+            let data_home_path = AsRef::<Path>::as_ref(data_home.as_os_str());
+            return Ok(data_home_path.join("Trash"));
+        }
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        if !home.is_empty() {
+            let home_path = AsRef::<Path>::as_ref(home.as_os_str());
+            return Ok(home_path.join(".local/share/Trash"));
+        }
+    }
+    Err(Error::Unknown { description: "Neither the XDG_DATA_HOME nor the HOME environment variable was found".into() })
+}
+
+/// Returns the topdir of the file system that `path` is on, which is the directory its trash folders belong in.
+///
+/// This is the closest of `mnt_points` above `path` that is on the same device as the parent of `path`, so that
+/// the topdir is on the same file system as `path` even for bind mounts. Directories that aren't mount points,
+/// like nested btrfs subvolumes, are never topdirs, as their trash folders wouldn't be found again.
+///
+/// Falls back to the longest of `mnt_points` that contains `path` if none is on the same device, or if the
+/// devices can't be determined.
+fn get_topdir_of_path(path: &Path, mnt_points: &[MountPoint]) -> PathBuf {
+    // An item can't be its own topdir, even if it's a mount point itself.
+    let parent = path.parent().unwrap_or(path);
+    let Some(dev) = parent.ancestors().find_map(|dir| dir.symlink_metadata().ok()).map(|metadata| metadata.dev())
+    else {
+        return get_first_topdir_containing_path(parent, mnt_points).to_owned();
+    };
+    parent
+        .ancestors()
+        .filter(|dir| mnt_points.iter().any(|mp| mp.mnt_dir == *dir))
+        .find(|dir| dir.symlink_metadata().is_ok_and(|metadata| metadata.dev() == dev))
+        .unwrap_or_else(|| get_first_topdir_containing_path(parent, mnt_points))
+        .to_owned()
+}
+
+/// Whether `a` and `b` are the same path, either literally or after resolving symbolic links.
+fn is_same_path(a: &Path, b: &Path) -> bool {
+    a == b || a.canonicalize().is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+fn get_first_topdir_containing_path<'a>(path: &Path, mnt_points: &'a [MountPoint]) -> &'a Path {
+    let root: &'static Path = Path::new("/");
+    mnt_points.iter().map(|mp| mp.mnt_dir.as_path()).find(|mount_path| path.starts_with(mount_path)).unwrap_or(root)
+}
+
+/// Canonicalize a path. If the path doesn't exist, the canonical form of the
+/// path is determined by looking at its parent directories.
+fn canonicalize_path_or_parents(mut path: &Path) -> Result<PathBuf, Error> {
+    let mut popped_path_components = vec![];
+
+    loop {
+        match path.canonicalize() {
+            Ok(canonical) => {
+                return Ok(popped_path_components.iter().rev().fold(canonical, |acc, component| acc.join(component)));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let file_name = path.file_name().ok_or(Error::CanonicalizePath { original: path.to_owned() })?;
+                popped_path_components.push(file_name.to_owned());
+                path = path.parent().ok_or(Error::CanonicalizePath { original: path.to_owned() })?;
+            }
+            Err(e) => return Err(Error::FileSystem { path: path.to_owned(), source: e }),
+        }
+    }
+}
+
+struct MountPoint {
+    mnt_dir: PathBuf,
+    _mnt_type: String,
+    _mnt_fsname: String,
+}
+
+/// Sorted by longest path first
+fn get_sorted_mount_points() -> Result<Vec<MountPoint>, Error> {
+    Ok(sort_mount_points(get_mount_points()?))
+}
+
+/// Sorted by longest path first
+fn sort_mount_points(mut mount_points: Vec<MountPoint>) -> Vec<MountPoint> {
+    mount_points.sort_unstable_by(|a, b| {
+        let a = a.mnt_dir.as_os_str().as_bytes().len();
+        let b = b.mnt_dir.as_os_str().as_bytes().len();
+        a.cmp(&b).reverse()
+    });
+    mount_points
+}
+
+#[cfg(target_os = "linux")]
+fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
+    // The mount tables are parsed directly as `libc::getmntent` isn't thread-safe.
+    // `/proc/self/mountinfo` unambiguously describes the mounts of this process' mount namespace,
+    // while the other tables may be missing in some containers.
+    for path in ["/proc/self/mountinfo", "/proc/mounts", "/etc/mtab"] {
+        match std::fs::read(path) {
+            Ok(content) => {
+                let mount_points = if path == "/proc/self/mountinfo" {
+                    mount_info::parse_mountinfo(&content)
+                } else {
+                    mount_info::parse_mounts(&content)
+                };
+                if mount_points.is_empty() {
+                    debug!("The mount table at '{}' didn't contain any mount points", path);
+                    continue;
+                }
+                return Ok(mount_points);
+            }
+            Err(e) => debug!("The mount table at '{}' could not be read: {}", path, e),
+        }
+    }
+    Err(Error::Unknown {
+        description: "None of '/proc/self/mountinfo', '/proc/mounts' and '/etc/mtab' contained any mount points."
+            .into(),
+    })
+}
+
+#[cfg(any(target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
+    use once_cell::sync::Lazy;
+    use std::sync::Mutex;
+
+    // The getmntinfo() function writes the array of structures to an internal
+    // static object and returns a pointer to that object.  Subsequent calls to
+    // getmntinfo() will modify the same object. This means that the function is
+    // not threadsafe. To help prevent multiple threads using it concurrently
+    // via get_mount_points a Mutex is used.
+    // We understand that threads can still call `libc::getmntinfo(…)` directly
+    // to bypass the lock and trigger UB.
+    static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+    let _lock = LOCK.lock().unwrap();
+
+    fn c_buf_to_str(buf: &[libc::c_char]) -> Option<&str> {
+        let buf: &[u8] = unsafe { std::slice::from_raw_parts(buf.as_ptr() as _, buf.len()) };
+        if let Some(pos) = buf.iter().position(|x| *x == 0) {
+            // Shrink buffer to omit the null bytes
+            std::str::from_utf8(&buf[..pos]).ok()
+        } else {
+            std::str::from_utf8(buf).ok()
+        }
+    }
+    let mut fs_infos: *mut libc::statfs = std::ptr::null_mut();
+    let count = unsafe { libc::getmntinfo(&mut fs_infos, libc::MNT_WAIT) };
+    if count < 1 {
+        return Ok(Vec::new());
+    }
+    let fs_infos: &[libc::statfs] = unsafe { std::slice::from_raw_parts(fs_infos as _, count as _) };
+
+    let mut result = Vec::new();
+    for fs_info in fs_infos {
+        if fs_info.f_mntfromname[0] == 0 || fs_info.f_mntonname[0] == 0 {
+            // If we have missing information, no need to look any further...
+            continue;
+        }
+        let fs_type = c_buf_to_str(&fs_info.f_fstypename).unwrap_or_default();
+        let mount_to = match c_buf_to_str(&fs_info.f_mntonname) {
+            Some(m) => m,
+            None => {
+                debug!("Cannot get disk mount point, ignoring it.");
+                continue;
+            }
+        };
+        let mount_from = c_buf_to_str(&fs_info.f_mntfromname).unwrap_or_default();
+
+        let mount_point =
+            MountPoint { mnt_dir: mount_to.into(), _mnt_fsname: mount_from.into(), _mnt_type: fs_type.into() };
+        result.push(mount_point);
+    }
+    Ok(result)
+}
+
+#[cfg(target_os = "netbsd")]
+fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
+    use once_cell::sync::Lazy;
+    use std::sync::Mutex;
+
+    // The getmntinfo() function writes the array of structures to an internal
+    // static object and returns a pointer to that object.  Subsequent calls to
+    // getmntinfo() will modify the same object. This means that the function is
+    // not threadsafe. To help prevent multiple threads using it concurrently
+    // via get_mount_points a Mutex is used.
+    // We understand that threads can still call `libc::getmntinfo(…)` directly
+    // to bypass the lock and trigger UB.
+    // NetBSD does not support statfs since 2005, so we need to use statvfs instead.
+    static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+    let _lock = LOCK.lock().unwrap();
+
+    fn c_buf_to_str(buf: &[libc::c_char]) -> Option<&str> {
+        let buf: &[u8] = unsafe { std::slice::from_raw_parts(buf.as_ptr() as _, buf.len()) };
+        if let Some(pos) = buf.iter().position(|x| *x == 0) {
+            // Shrink buffer to omit the null bytes
+            std::str::from_utf8(&buf[..pos]).ok()
+        } else {
+            std::str::from_utf8(buf).ok()
+        }
+    }
+    let mut fs_infos: *mut libc::statvfs = std::ptr::null_mut();
+    let count = unsafe { libc::getmntinfo(&mut fs_infos, libc::MNT_WAIT) };
+    if count < 1 {
+        return Ok(Vec::new());
+    }
+    let fs_infos: &[libc::statvfs] = unsafe { std::slice::from_raw_parts(fs_infos as _, count as _) };
+
+    let mut result = Vec::new();
+    for fs_info in fs_infos {
+        if fs_info.f_mntfromname[0] == 0 || fs_info.f_mntonname[0] == 0 {
+            // If we have missing information, no need to look any further...
+            continue;
+        }
+        let fs_type = c_buf_to_str(&fs_info.f_fstypename).unwrap_or_default();
+        let mount_to = match c_buf_to_str(&fs_info.f_mntonname) {
+            Some(m) => m,
+            None => {
+                debug!("Cannot get disk mount point, ignoring it.");
+                continue;
+            }
+        };
+        let mount_from = c_buf_to_str(&fs_info.f_mntfromname).unwrap_or_default();
+
+        let mount_point =
+            MountPoint { mnt_dir: mount_to.into(), _mnt_fsname: mount_from.into(), _mnt_type: fs_type.into() };
+        result.push(mount_point);
+    }
+    Ok(result)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+)))]
+fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
+    // On platforms that don't have support yet, return an error
+    Err(Error::Unknown { description: "Mount points cannot be determined on this operating system".into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+    use std::{
+        collections::{hash_map::Entry, HashMap},
+        env,
+        ffi::{OsStr, OsString},
+        fmt,
+        fs::File,
+        io::ErrorKind,
+        os::unix::{
+            self,
+            ffi::OsStringExt,
+            fs::{MetadataExt, PermissionsExt},
+        },
+        path::{Path, PathBuf},
+        process::Command,
+    };
+
+    use log::warn;
+
+    use crate::{
+        canonicalize_paths, delete, delete_all,
+        os_limited::{list, list_with_errors, metadata, purge_all, restore_all},
+        platform::encode_uri_path,
+        tests::get_unique_name,
+        Error, ItemErrorKind, TrashContext,
+    };
+
+    use super::{
+        canonicalize_path_or_parents, decode_uri_path, execute_on_mounted_trash_folders, fs_error, get_topdir_of_path,
+        home_trash, is_partial_source_removal, move_items_no_replace, move_to_trash, parse_directory_sizes,
+        progress::ItemProgress, read_directory_sizes, serialize_directory_sizes, update_directory_sizes,
+        CancellationToken, DirectorySize, FsckRepair, JournalLocation, MountPoint, PartialSourceRemoval, Progress,
+        TrashContextExtFreedesktop, TrashInfo, TrashInfoError,
+    };
+
+    /// Returns a context that doesn't touch the system's trash: its 'home trash' is `Trash` within a new
+    /// temporary directory, and its topdirs are the given directories within it, which are created as well.
+    /// The temporary directory is removed when the returned guard is dropped.
+    pub(super) fn isolated_context(topdirs: &[&str]) -> (tempfile::TempDir, PathBuf, TrashContext) {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        let topdirs: Vec<_> = topdirs.iter().map(|topdir| root.join(topdir)).collect();
+        for topdir in &topdirs {
+            std::fs::create_dir_all(topdir).unwrap();
+        }
+        let mut ctx = TrashContext::default();
+        ctx.set_home_trash(Some(root.join("Trash")));
+        ctx.set_topdirs(Some(topdirs));
+        (tmp, root, ctx)
+    }
+
+    #[test]
+    #[serial]
+    fn test_list() {
+        crate::tests::init_logging();
+
+        let file_name_prefix = get_unique_name();
+        let batches: usize = 2;
+        let files_per_batch: usize = 3;
+        let names: Vec<OsString> = (0..files_per_batch).map(|i| format!("{}#{}", file_name_prefix, i).into()).collect();
+        for _ in 0..batches {
+            for path in &names {
+                File::create_new(path).unwrap();
+            }
+            // eprintln!("Deleting {:?}", names);
+            let result = delete_all_using_system_program(&names);
+            if let Err(SystemTrashError::NoTrashProgram) = &result {
+                // For example may be the case on build systems that don't have a destop environment
+                warn!("No system default trashing utility was found, using this crate's implementation");
+                delete_all(&names).unwrap();
+            } else {
+                result.unwrap();
+            }
+        }
+        let items = list().unwrap();
+        let items: HashMap<_, Vec<_>> = items
+            .into_iter()
+            .filter(|x| x.name.as_encoded_bytes().starts_with(file_name_prefix.as_bytes()))
+            .fold(HashMap::new(), |mut map, x| {
+                match map.entry(x.name.clone()) {
+                    Entry::Occupied(mut entry) => {
+                        entry.get_mut().push(x);
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(vec![x]);
+                    }
+                }
+                map
+            });
+        for name in names {
+            match items.get(&name) {
+                Some(items) => assert_eq!(items.len(), batches),
+                None => panic!("ERROR Could not find '{:?}' in {:#?}", name, items),
+            }
+        }
+
+        // Let's try to purge all the items we just created but ignore any errors
+        // as this test should succeed as long as `list` works properly.
+        let _ = purge_all(items.into_values().flatten());
+    }
+
+    #[test]
+    #[serial]
+    fn test_broken_symlinks() {
+        crate::tests::init_logging();
+
+        let file_name_prefix = get_unique_name();
+        let file_count = 2;
+        let names: Vec<OsString> = (0..file_count).map(|i| format!("{}#{}", file_name_prefix, i).into()).collect();
+        let symlink_names: Vec<OsString> = names.iter().map(|name| format!("{:?}-symlink", name).into()).collect();
+
+        // Test file symbolic link and directory symbolic link
+        File::create_new(&names[0]).unwrap();
+        std::fs::create_dir(&names[1]).unwrap();
+
+        for (i, (name, symlink)) in names.iter().zip(&symlink_names).enumerate() {
+            // Create symbolic link
+            unix::fs::symlink(name, symlink).unwrap();
+
+            // Break the symbolic link
+            if i == 0 {
+                std::fs::remove_file(name).unwrap();
+            } else {
+                std::fs::remove_dir(name).unwrap();
+            }
+
+            // Delete and Restore it without errors
+            delete(symlink).unwrap();
+            let items = list().unwrap();
+            let item = items.into_iter().find(|it| it.name == *symlink).unwrap();
+            restore_all([item.clone()]).expect("The broken symbolic link should be restored successfully.");
+
+            // Delete and Purge it without errors
+            delete(symlink).unwrap();
+            purge_all([item]).expect("The broken symbolic link should be purged successfully.");
+        }
+    }
+
+    #[test]
+    fn test_move_to_trash_relative_paths() {
+        let topdir = tempfile::tempdir().unwrap();
+        let trash_folder = topdir.path().join(".Trash-1000");
+        std::fs::create_dir_all(topdir.path().join("some/dir")).unwrap();
+
+        let read_info_path = |name: &str| {
+            let info = std::fs::read(trash_folder.join("info").join(format!("{name}.trashinfo"))).unwrap();
+            TrashInfo::parse(&info).unwrap().path
+        };
+        for (name, relative_to, expected) in [
+            ("relative", Some(topdir.path()), PathBuf::from("some/dir/relative")),
+            ("absolute", None, topdir.path().join("some/dir/absolute")),
+        ] {
+            let src = topdir.path().join("some/dir").join(name);
+            File::create_new(&src).unwrap();
+            move_to_trash(&src, &trash_folder, relative_to, &mut ItemProgress::untracked(&src)).unwrap();
+            assert_eq!(read_info_path(name), expected);
+            assert!(trash_folder.join("files").join(name).is_file());
+        }
+    }
+
+    #[test]
+    fn test_isolated_context() {
+        let (_tmp, home, ctx) = isolated_context(&["mnt"]);
+        let topdir = home.join("mnt");
+
+        let (in_home, in_topdir) = (home.join("file"), topdir.join("file"));
+        File::create_new(&in_home).unwrap();
+        File::create_new(&in_topdir).unwrap();
+        ctx.delete_all([&in_home, &in_topdir]).unwrap();
+        assert!(!in_home.exists() && !in_topdir.exists());
+        let uid = unsafe { libc::getuid() };
+        assert!(home.join("Trash/files/file").is_file());
+        assert!(topdir.join(format!(".Trash-{uid}/files/file")).is_file());
+        let info = std::fs::read(topdir.join(format!(".Trash-{uid}/info/file.trashinfo"))).unwrap();
+        assert_eq!(TrashInfo::parse(&info).unwrap().path, in_topdir, "absolute paths are recorded by default");
+
+        let folders = ctx.trash_folders().unwrap();
+        assert_eq!(folders, [home.join("Trash"), topdir.join(format!(".Trash-{uid}"))].into_iter().collect());
+        let mut items = ctx.list().unwrap();
+        items.sort_by(|a, b| a.original_parent.cmp(&b.original_parent));
+        assert_eq!(items.iter().map(|item| item.original_path()).collect::<Vec<_>>(), [in_home.clone(), in_topdir]);
+
+        let in_topdir = items.pop().unwrap();
+        restore_all([in_topdir]).unwrap();
+        assert!(topdir.join("file").is_file());
+        purge_all(&items).unwrap();
+        assert!(!in_home.exists());
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_delete_all_with_items() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        let paths = [root.join("a/file"), root.join("b/file")];
+        for path in &paths {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create_new(path).unwrap();
+        }
+
+        let items = ctx.delete_all_with_items(&paths).unwrap();
+        assert_eq!(items.iter().map(|item| item.original_path()).collect::<Vec<_>>(), paths);
+        assert_eq!(items[0].id, root.join("Trash/info/file.trashinfo").into_os_string());
+        assert_eq!(items[1].id, root.join("Trash/info/file.2.trashinfo").into_os_string());
+        let listed = ctx.list().unwrap();
+        for item in &items {
+            let listed = listed.iter().find(|listed| *listed == item).expect("items are listed with the same id");
+            assert_eq!(listed.original_path(), item.original_path());
+            assert_eq!(listed.time_deleted, item.time_deleted);
+        }
+
+        ctx.restore_all(items).unwrap();
+        assert!(paths.iter().all(|path| path.is_file()));
+    }
+
+    #[test]
+    fn test_restore_to() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        std::fs::create_dir_all(root.join("dir/sub")).unwrap();
+        std::fs::write(root.join("dir/sub/file"), "content").unwrap();
+        std::fs::write(root.join("file"), "file").unwrap();
+        std::fs::write(root.join("taken"), "taken").unwrap();
+        let mut items = ctx.delete_all_with_items([root.join("dir"), root.join("file")]).unwrap();
+        let file = items.pop().unwrap();
+        let dir = items.pop().unwrap();
+
+        match ctx.restore_to(file.clone(), root.join("taken")) {
+            Err(Error::RestoreCollision { path, remaining_items }) => {
+                assert_eq!(path, root.join("taken"));
+                assert_eq!(remaining_items, std::slice::from_ref(&file));
+            }
+            other => panic!("expected a collision, got {other:?}"),
+        }
+        assert_eq!(std::fs::read_to_string(root.join("taken")).unwrap(), "taken");
+        assert_eq!(ctx.list().unwrap().len(), 2);
+
+        ctx.restore_all_to([(dir, root.join("new/parent/dir")), (file, root.join("copy of file"))]).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("new/parent/dir/sub/file")).unwrap(), "content");
+        assert_eq!(std::fs::read_to_string(root.join("copy of file")).unwrap(), "file");
+        assert!(!root.join("dir").exists());
+        assert!(ctx.list().unwrap().is_empty());
+        assert!(std::fs::read_dir(root.join("Trash/files")).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn test_list_iter() {
+        let (_tmp, home, ctx) = isolated_context(&["mnt"]);
+        let topdir = home.join("mnt");
+        assert_eq!(ctx.list_iter().unwrap().count(), 0);
+        assert!(ctx.is_empty().unwrap(), "a missing trash is empty");
+
+        let paths = [home.join("a"), home.join("b"), topdir.join("c")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        let items = ctx.delete_all_with_items(&paths).unwrap();
+        assert!(!ctx.is_empty().unwrap());
+        let mut iter = ctx.list_iter().unwrap();
+        assert!(items.contains(&iter.next().unwrap().unwrap()), "the first item is yielded on its own");
+        drop(iter);
+
+        std::fs::write(home.join("Trash/info/unparsable.trashinfo"), "garbage").unwrap();
+        let (mut listed, mut errors) = (Vec::new(), Vec::new());
+        for item in ctx.list_iter().unwrap() {
+            match item {
+                Ok(item) => listed.push(item.original_path()),
+                Err(error) => errors.push(error),
+            }
+        }
+        listed.sort();
+        assert_eq!(listed, paths);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].kind, ItemErrorKind::InvalidInfo(_)));
+
+        ctx.purge_all(items).unwrap();
+        assert!(!ctx.is_empty().unwrap(), "a malformed entry is still in the trash");
+        std::fs::remove_file(home.join("Trash/info/unparsable.trashinfo")).unwrap();
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_is_empty_with_orphans() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        std::fs::create_dir_all(root.join("Trash/files")).unwrap();
+        std::fs::write(root.join("Trash/files/orphan"), "").unwrap();
+        assert!(ctx.list().unwrap().is_empty());
+        assert!(!ctx.is_empty().unwrap(), "an orphan is still in the trash");
+
+        assert!(ctx.empty().unwrap().is_empty());
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn test_list_reports_malformed_entries() {
+        crate::tests::init_logging();
+
+        let info_folder = home_trash().unwrap().join("info");
+        std::fs::create_dir_all(&info_folder).unwrap();
+        let prefix = get_unique_name();
+        let info_files = [
+            (format!("{prefix}-no-equals.trashinfo"), "[Trash Info]\nPath\n".to_owned()),
+            (format!("{prefix}-root.trashinfo"), "[Trash Info]\nPath=/\n".to_owned()),
+            (format!("{prefix}-equals.trashinfo"), format!("[Trash Info]\nPath=/tmp/{prefix}=value\n")),
+        ];
+        for (name, content) in &info_files {
+            std::fs::write(info_folder.join(name), content).unwrap();
+        }
+
+        let listed = list().unwrap();
+        let (items, errors) = list_with_errors().unwrap();
+        for (name, _) in &info_files {
+            std::fs::remove_file(info_folder.join(name)).unwrap();
+        }
+
+        assert_eq!(listed.len(), items.len(), "list() skips exactly the entries that are reported as errors");
+        let item = items.iter().find(|item| item.original_parent == Path::new("/tmp")).unwrap();
+        assert_eq!(item.name, OsString::from(format!("{prefix}=value")), "values may contain '='");
+
+        let error_of = |name: &str| errors.iter().find(|e| e.path == info_folder.join(name)).map(|e| &e.kind);
+        assert!(matches!(
+            error_of(&info_files[0].0),
+            Some(ItemErrorKind::InvalidInfo(TrashInfoError::InvalidLine { line: 2 }))
+        ));
+        assert!(
+            matches!(error_of(&info_files[1].0), Some(ItemErrorKind::InvalidOriginalPath(path)) if path == Path::new("/"))
+        );
+    }
+
+    #[test]
+    fn test_directory_sizes_cache() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        let names = ["first", "second"];
+        for name in names {
+            std::fs::create_dir(root.join(name)).unwrap();
+            std::fs::write(root.join(name).join("content"), vec![1u8; 10_000]).unwrap();
+        }
+        ctx.delete_all(names.map(|name| root.join(name))).unwrap();
+
+        let mut items = ctx.list().unwrap();
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(items.len(), 2);
+        let trash_folder = root.join("Trash");
+        let in_trash_name = |item: &crate::TrashItem| Path::new(&item.id).file_stem().unwrap().to_owned();
+        let cached =
+            |name: &OsStr| read_directory_sizes(&trash_folder).unwrap().into_iter().find(|entry| entry.name == name);
+
+        for item in &items {
+            let entry = cached(&in_trash_name(item)).expect("trashed directories are added to the cache");
+            assert!(entry.size >= 10_000, "the cache holds the recursive size, got {}", entry.size);
+            let info_mtime = std::fs::metadata(&item.id).unwrap().mtime();
+            assert_eq!(entry.mtime, info_mtime, "the cache entry refers to the info file's mtime");
+        }
+
+        let [restored, purged] = [items[0].clone(), items[1].clone()];
+        ctx.restore_all([restored.clone()]).unwrap();
+        assert!(cached(&in_trash_name(&restored)).is_none(), "restored directories are removed from the cache");
+
+        ctx.purge_all([purged.clone()]).unwrap();
+        assert!(cached(&in_trash_name(&purged)).is_none(), "purged directories are removed from the cache");
+    }
+
+    #[test]
+    fn test_metadata_disk_usage() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        let dir = root.join("dir");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a"), vec![1u8; 10_000]).unwrap();
+        std::fs::write(dir.join("sub/b"), vec![2u8; 20_000]).unwrap();
+        std::fs::write(root.join("file"), vec![3u8; 10_000]).unwrap();
+        let items = ctx.delete_all_with_items([dir, root.join("file")]).unwrap();
+        let trash_folder = root.join("Trash");
+
+        let cached = metadata(&items[0]).unwrap().disk_usage.unwrap();
+        assert!(cached.bytes >= 30_000, "all nested files are accounted for, got {}", cached.bytes);
+        assert_eq!(cached.files, None, "the size was taken from the cache which doesn't know the file count");
+        let file = metadata(&items[1]).unwrap().disk_usage.unwrap();
+        assert!(file.bytes >= 10_000);
+        assert_eq!(file.files, Some(1));
+
+        update_directory_sizes(&trash_folder, |entries| {
+            entries.clear();
+            true
+        })
+        .unwrap();
+        assert_eq!(metadata(&items[0]).unwrap().disk_usage, None, "uncached directories aren't walked");
+        assert!(read_directory_sizes(&trash_folder).unwrap().is_empty(), "metadata doesn't write the cache");
+
+        let findings = ctx.fsck(&FsckRepair::Delete).unwrap();
+        assert!(findings.is_empty(), "{findings:#?}");
+        assert_eq!(metadata(&items[0]).unwrap().disk_usage, Some(cached), "repairing the trash fills the cache");
+    }
+
+    #[test]
+    fn directory_sizes_roundtrip() {
+        let entries = vec![
+            DirectorySize { size: 4096, mtime: 1_700_000_000, name: "plain".into() },
+            DirectorySize { size: 0, mtime: -1, name: "with space and % sign".into() },
+            DirectorySize { size: u64::MAX, mtime: 42, name: OsString::from_vec(vec![b'a', 168]) },
+        ];
+        let content = serialize_directory_sizes(&entries);
+        assert_eq!(content.split(|b| *b == b'\n').next().unwrap(), b"4096 1700000000 plain");
+        assert_eq!(parse_directory_sizes(&content), entries);
+    }
+
+    #[test]
+    fn directory_sizes_skip_malformed_lines() {
+        let content = b"12 34 valid\nnot-a-number 34 name\n12 34\n12 34 \n\n56 78 also%20valid\n";
+        let names: Vec<_> = parse_directory_sizes(content).into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, [OsString::from("valid"), OsString::from("also valid")]);
+    }
+
+    #[test]
+    fn mounted_trash_folder_is_created_in_valid_admin_trash() {
+        let collect = |topdir: &Path| {
+            let mut folders = Vec::new();
+            execute_on_mounted_trash_folders(1000, topdir, true, true, |trash_path| {
+                folders.push(trash_path);
+                Ok(())
+            })
+            .unwrap();
+            folders
+        };
+
+        let topdir = tempfile::tempdir().unwrap();
+        let admin_trash = topdir.path().join(".Trash");
+        std::fs::create_dir(&admin_trash).unwrap();
+        std::fs::set_permissions(&admin_trash, std::fs::Permissions::from_mode(0o1777)).unwrap();
+        assert_eq!(collect(topdir.path()), [admin_trash.join("1000")]);
+        let mode = std::fs::metadata(admin_trash.join("1000")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert!(!topdir.path().join(".Trash-1000").exists());
+
+        // Without the sticky bit, `.Trash` must not be used.
+        let topdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(topdir.path().join(".Trash")).unwrap();
+        assert_eq!(collect(topdir.path()), [topdir.path().join(".Trash-1000")]);
+        assert!(!topdir.path().join(".Trash/1000").exists());
+    }
+
+    #[test]
+    fn topdir_of_path() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path().canonicalize().unwrap();
+        let nested = root.join("mnt/nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        File::create_new(nested.join("file")).unwrap();
+        let mount =
+            |dir: &Path| MountPoint { mnt_dir: dir.to_owned(), _mnt_type: String::new(), _mnt_fsname: String::new() };
+        let mnt_points = [mount(&nested.join("file")), mount(&root.join("mnt")), mount(Path::new("/"))];
+
+        // Directories that aren't mount points are never topdirs, and neither is the item itself.
+        assert_eq!(get_topdir_of_path(&nested.join("missing/file"), &mnt_points), root.join("mnt"));
+        assert_eq!(get_topdir_of_path(&nested.join("file"), &mnt_points), root.join("mnt"));
+        assert_eq!(get_topdir_of_path(&root.join("mnt"), &mnt_points), Path::new("/"));
+        assert_eq!(get_topdir_of_path(&nested, &[]), Path::new("/"));
+    }
+
+    #[test]
+    fn items_outside_of_all_topdirs_go_into_the_home_trash() {
+        let (_tmp, root, ctx) = isolated_context(&["mnt"]);
+        // Preferably on another file system than the 'home trash'.
+        let outside = tempfile::tempdir_in("/dev/shm").or_else(|_| tempfile::tempdir()).unwrap();
+        let outside = outside.path().canonicalize().unwrap();
+        std::fs::create_dir(outside.join("dir")).unwrap();
+        std::fs::write(outside.join("dir/file"), "content").unwrap();
+
+        let items = ctx.delete_all_with_items([outside.join("dir")]).unwrap();
+        assert_eq!(items[0].id, root.join("Trash/info/dir.trashinfo").into_os_string());
+        assert_eq!(ctx.list().unwrap(), items);
+        ctx.restore_all(items).unwrap();
+        assert_eq!(std::fs::read_to_string(outside.join("dir/file")).unwrap(), "content");
+    }
+
+    #[test]
+    fn partially_removed_item_stays_in_the_trash() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        let Ok(other_fs) = tempfile::tempdir_in("/dev/shm") else { return };
+        let src = other_fs.path().canonicalize().unwrap().join("dir");
+        std::fs::create_dir(&src).unwrap();
+        if std::fs::metadata(&src).unwrap().dev() == std::fs::metadata(&root).unwrap().dev() {
+            // Only items that are copied across file systems can be partially removed.
+            return;
+        }
+        for name in ["a", "b"] {
+            std::fs::write(src.join(name), name).unwrap();
+        }
+        ctx.set_journal(Some(JournalLocation::Path(root.join("journal"))));
+        // Cancelling once the directory is copied interrupts the removal of its source.
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        ctx.set_cancellation_token(Some(token));
+        ctx.set_progress_observer(Some(std::sync::Arc::new(move |progress: &Progress<'_>| {
+            if progress.entries == 3 {
+                cancel.cancel();
+            }
+        })));
+
+        let items = match ctx.delete_all_with_items([&src]) {
+            Err(Error::PartialSourceRemoval { path, items, .. }) if path == src => items,
+            other => panic!("unexpected result {other:?}"),
+        };
+        assert!(src.join("a").exists(), "the removal was interrupted");
+        assert_eq!(ctx.list().unwrap(), items);
+        let batches = ctx.journal_batches().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].items, items, "the item can be undone");
+        let directory_sizes = read_directory_sizes(&root.join("Trash")).unwrap();
+        assert_eq!(directory_sizes.len(), 1);
+        assert_eq!(directory_sizes[0].name, "dir");
+    }
+
+    #[test]
+    fn failed_move_leaves_no_placeholder() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("dir");
+        std::fs::create_dir(&src).unwrap();
+        // A directory can't be moved into itself.
+        let (path, error) =
+            move_items_no_replace(&src, src.join("sub"), &mut ItemProgress::untracked(&src)).unwrap_err();
+        assert_eq!(path, src);
+        assert!(!is_partial_source_removal(&error));
+        assert_eq!(std::fs::read_dir(&src).unwrap().count(), 0, "the placeholder was removed");
+    }
+
+    #[test]
+    fn partial_source_removal_error() {
+        let inner = PartialSourceRemoval {
+            copy: "/trash/files/item".into(),
+            source: std::io::Error::from(ErrorKind::PermissionDenied),
+        };
+        let error = std::io::Error::new(ErrorKind::PermissionDenied, inner);
+        assert!(is_partial_source_removal(&error));
+        match fs_error("/item", error) {
+            Error::PartialSourceRemoval { path, copy, source, items } => {
+                assert_eq!(path, Path::new("/item"));
+                assert!(items.is_empty());
+                assert_eq!(copy, Path::new("/trash/files/item"));
+                assert_eq!(source.kind(), ErrorKind::PermissionDenied);
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        assert!(matches!(fs_error("/item", ErrorKind::NotFound.into()), Error::FileSystem { .. }));
+    }
+
+    #[test]
+    fn uri_enc_dec_roundtrip() {
+        let fake = format!("/tmp/{}", get_unique_name());
+        let path = decode_uri_path(&fake);
+
+        assert_eq!(path.to_str().expect("Path is valid Unicode"), fake, "Decoded path shouldn't be different");
+
+        let encoded = encode_uri_path(&path);
+        assert_eq!(encoded, fake, "URL encoded alphanumeric String shouldn't change");
+    }
+
+    #[test]
+    fn uri_enc_dec_roundtrip_invalid_unicode() {
+        let base = OsStr::new(&format!("/tmp/{}/", get_unique_name())).to_os_string();
+
+        // Add invalid UTF-8 byte
+        let mut bytes = base.into_encoded_bytes();
+        bytes.push(168);
+        let fake = OsString::from_vec(bytes);
+        assert!(fake.to_str().is_none(), "Invalid Unicode cannot be a Rust String");
+
+        let path = decode_uri_path(&fake);
+        assert_eq!(path.as_os_str().as_encoded_bytes(), fake.as_encoded_bytes());
+
+        // Shouldn't panic
+        encode_uri_path(&path);
+    }
+
+    #[test]
+    fn test_canonicalize_path_or_parents() {
+        enum CanonicalizeFixture<'a> {
+            Dir(&'a str),
+            File(&'a str),
+            Symlink { path: &'a str, target: &'a str },
+            Chmod { path: &'a str, mode: u32 },
+        }
+
+        enum CanonicalizeExpectation<'a> {
+            Canonical(&'a str),
+            PermissionDenied,
+        }
+
+        struct CanonicalizeCase<'a> {
+            name: &'a str,
+            setup: &'a [CanonicalizeFixture<'a>],
+            input: &'a str,
+            expected: CanonicalizeExpectation<'a>,
+            teardown: &'a [CanonicalizeFixture<'a>],
+        }
+
+        fn resolve(case_root: &Path, relative_path: &str) -> PathBuf {
+            case_root.join(relative_path)
+        }
+
+        fn apply_fixtures(case_root: &Path, fixtures: &[CanonicalizeFixture<'_>]) {
+            for fixture in fixtures {
+                match fixture {
+                    CanonicalizeFixture::Dir(path) => {
+                        std::fs::create_dir_all(resolve(case_root, path)).unwrap();
+                    }
+                    CanonicalizeFixture::File(path) => {
+                        let path = resolve(case_root, path);
+                        if let Some(parent) = path.parent() {
+                            std::fs::create_dir_all(parent).unwrap();
+                        }
+                        File::create_new(path).unwrap();
+                    }
+                    CanonicalizeFixture::Symlink { path, target } => {
+                        let path = resolve(case_root, path);
+                        if let Some(parent) = path.parent() {
+                            std::fs::create_dir_all(parent).unwrap();
+                        }
+                        unix::fs::symlink(resolve(case_root, target), path).unwrap();
+                    }
+                    CanonicalizeFixture::Chmod { path, mode } => {
+                        std::fs::set_permissions(resolve(case_root, path), std::fs::Permissions::from_mode(*mode))
+                            .unwrap();
+                    }
+                }
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let cases = [
+            CanonicalizeCase {
+                name: "path exists",
+                setup: &[
+                    CanonicalizeFixture::Dir("existing-parent"),
+                    CanonicalizeFixture::File("existing-parent/existing-file"),
+                ],
+                input: "existing-parent/existing-file",
+                expected: CanonicalizeExpectation::Canonical("existing-parent/existing-file"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "path doesn't exist",
+                setup: &[CanonicalizeFixture::Dir("existing-parent")],
+                input: "existing-parent/missing-file",
+                expected: CanonicalizeExpectation::Canonical("existing-parent/missing-file"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "path is symlink",
+                setup: &[
+                    CanonicalizeFixture::Dir("existing-parent"),
+                    CanonicalizeFixture::File("existing-parent/path-symlink-target"),
+                    CanonicalizeFixture::Symlink {
+                        path: "existing-parent/path-symlink",
+                        target: "existing-parent/path-symlink-target",
+                    },
+                ],
+                input: "existing-parent/path-symlink",
+                expected: CanonicalizeExpectation::Canonical("existing-parent/path-symlink-target"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "no perms to path",
+                setup: &[
+                    CanonicalizeFixture::Dir("no-perms-path-parent/locked-path"),
+                    CanonicalizeFixture::Chmod { path: "no-perms-path-parent/locked-path", mode: 0o000 },
+                ],
+                input: "no-perms-path-parent/locked-path/child",
+                expected: CanonicalizeExpectation::PermissionDenied,
+                teardown: &[CanonicalizeFixture::Chmod { path: "no-perms-path-parent/locked-path", mode: 0o700 }],
+            },
+            CanonicalizeCase {
+                name: "parent doesn't exist",
+                setup: &[CanonicalizeFixture::Dir("parent-missing-grandparent")],
+                input: "parent-missing-grandparent/missing-parent/leaf",
+                expected: CanonicalizeExpectation::Canonical("parent-missing-grandparent/missing-parent/leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "parent is symlink",
+                setup: &[
+                    CanonicalizeFixture::Dir("parent-symlink-target"),
+                    CanonicalizeFixture::File("parent-symlink-target/leaf"),
+                    CanonicalizeFixture::Dir("parent-symlink-grandparent"),
+                    CanonicalizeFixture::Symlink {
+                        path: "parent-symlink-grandparent/parent-link",
+                        target: "parent-symlink-target",
+                    },
+                ],
+                input: "parent-symlink-grandparent/parent-link/leaf",
+                expected: CanonicalizeExpectation::Canonical("parent-symlink-target/leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "parent is symlink but path doesn't exist",
+                setup: &[
+                    CanonicalizeFixture::Dir("parent-symlink-target"),
+                    CanonicalizeFixture::Dir("parent-symlink-grandparent"),
+                    CanonicalizeFixture::Symlink {
+                        path: "parent-symlink-grandparent/parent-link",
+                        target: "parent-symlink-target",
+                    },
+                ],
+                input: "parent-symlink-grandparent/parent-link/missing-leaf",
+                expected: CanonicalizeExpectation::Canonical("parent-symlink-target/missing-leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "no perms to parent",
+                setup: &[
+                    CanonicalizeFixture::Dir("no-perms-parent-grandparent/locked-parent"),
+                    CanonicalizeFixture::Chmod { path: "no-perms-parent-grandparent/locked-parent", mode: 0o000 },
+                ],
+                input: "no-perms-parent-grandparent/locked-parent/leaf/child",
+                expected: CanonicalizeExpectation::PermissionDenied,
+                teardown: &[CanonicalizeFixture::Chmod {
+                    path: "no-perms-parent-grandparent/locked-parent",
+                    mode: 0o700,
+                }],
+            },
+            CanonicalizeCase {
+                name: "grandparent doesn't exist",
+                setup: &[],
+                input: "missing-grandparent/parent/leaf",
+                expected: CanonicalizeExpectation::Canonical("missing-grandparent/parent/leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "grandparent is symlink",
+                setup: &[
+                    CanonicalizeFixture::Dir("grandparent-symlink-target/parent"),
+                    CanonicalizeFixture::File("grandparent-symlink-target/parent/leaf"),
+                    CanonicalizeFixture::Symlink { path: "grandparent-link", target: "grandparent-symlink-target" },
+                ],
+                input: "grandparent-link/parent/leaf",
+                expected: CanonicalizeExpectation::Canonical("grandparent-symlink-target/parent/leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "grandparent is symlink but path doesn't exist",
+                setup: &[
+                    CanonicalizeFixture::Dir("grandparent-symlink-target/parent"),
+                    CanonicalizeFixture::Symlink { path: "grandparent-link", target: "grandparent-symlink-target" },
+                ],
+                input: "grandparent-link/parent/missing-leaf",
+                expected: CanonicalizeExpectation::Canonical("grandparent-symlink-target/parent/missing-leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "grandparent is symlink but parent doesn't exist",
+                setup: &[
+                    CanonicalizeFixture::Dir("grandparent-symlink-target"),
+                    CanonicalizeFixture::Symlink { path: "grandparent-link", target: "grandparent-symlink-target" },
+                ],
+                input: "grandparent-link/missing-parent/leaf",
+                expected: CanonicalizeExpectation::Canonical("grandparent-symlink-target/missing-parent/leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "grandparent is symlink + parent is symlink",
+                setup: &[
+                    CanonicalizeFixture::Dir("grandparent-symlink-target"),
+                    CanonicalizeFixture::Dir("gp-parent-symlink-target"),
+                    CanonicalizeFixture::File("gp-parent-symlink-target/leaf"),
+                    CanonicalizeFixture::Symlink {
+                        path: "grandparent-symlink-target/parent-link",
+                        target: "gp-parent-symlink-target",
+                    },
+                    CanonicalizeFixture::Symlink { path: "grandparent-link", target: "grandparent-symlink-target" },
+                ],
+                input: "grandparent-link/parent-link/leaf",
+                expected: CanonicalizeExpectation::Canonical("gp-parent-symlink-target/leaf"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "grandparent is symlink + path is symlink",
+                setup: &[
+                    CanonicalizeFixture::Dir("grandparent-symlink-target/parent"),
+                    CanonicalizeFixture::File("gp-path-symlink-target"),
+                    CanonicalizeFixture::Symlink {
+                        path: "grandparent-symlink-target/parent/path-link",
+                        target: "gp-path-symlink-target",
+                    },
+                    CanonicalizeFixture::Symlink { path: "grandparent-link", target: "grandparent-symlink-target" },
+                ],
+                input: "grandparent-link/parent/path-link",
+                expected: CanonicalizeExpectation::Canonical("gp-path-symlink-target"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "grandparent is symlink + parent is symlink + path is symlink",
+                setup: &[
+                    CanonicalizeFixture::Dir("grandparent-symlink-target"),
+                    CanonicalizeFixture::Dir("gp-parent-path-parent-target"),
+                    CanonicalizeFixture::Dir("gp-parent-path-symlink-target-parent"),
+                    CanonicalizeFixture::File("gp-parent-path-symlink-target-parent/leaf-target"),
+                    CanonicalizeFixture::Symlink {
+                        path: "gp-parent-path-parent-target/path-link",
+                        target: "gp-parent-path-symlink-target-parent/leaf-target",
+                    },
+                    CanonicalizeFixture::Symlink {
+                        path: "grandparent-symlink-target/path-parent-link",
+                        target: "gp-parent-path-parent-target",
+                    },
+                    CanonicalizeFixture::Symlink { path: "grandparent-link", target: "grandparent-symlink-target" },
+                ],
+                input: "grandparent-link/path-parent-link/path-link",
+                expected: CanonicalizeExpectation::Canonical("gp-parent-path-symlink-target-parent/leaf-target"),
+                teardown: &[],
+            },
+            CanonicalizeCase {
+                name: "no perms to grandparent",
+                setup: &[
+                    CanonicalizeFixture::Dir("locked-grandparent/parent"),
+                    CanonicalizeFixture::Chmod { path: "locked-grandparent", mode: 0o000 },
+                ],
+                input: "locked-grandparent/parent/leaf/child",
+                expected: CanonicalizeExpectation::PermissionDenied,
+                teardown: &[CanonicalizeFixture::Chmod { path: "locked-grandparent", mode: 0o700 }],
+            },
+        ];
+
+        for (index, case) in cases.iter().enumerate() {
+            let case_root = root.join(format!("case-{index}"));
+            std::fs::create_dir(&case_root).unwrap();
+            apply_fixtures(&case_root, case.setup);
+
+            let input = resolve(&case_root, case.input);
+            match case.expected {
+                CanonicalizeExpectation::Canonical(expected) => {
+                    let expected = resolve(&case_root, expected);
+                    let canonical = canonicalize_path_or_parents(&input).unwrap_or_else(|err| {
+                        panic!("case `{}` unexpectedly failed for {:?}: {:?}", case.name, input, err);
+                    });
+                    assert_eq!(canonical, expected, "case `{}` produced an unexpected canonical path", case.name);
+                }
+                CanonicalizeExpectation::PermissionDenied => match canonicalize_path_or_parents(&input).unwrap_err() {
+                    Error::FileSystem { path, source } => {
+                        assert_eq!(path, input, "case `{}` returned an unexpected failing path", case.name);
+                        assert_eq!(
+                            source.kind(),
+                            ErrorKind::PermissionDenied,
+                            "case `{}` returned an unexpected error",
+                            case.name
+                        );
+                    }
+                    err => panic!("case `{}` returned an unexpected error for {:?}: {:?}", case.name, input, err),
+                },
+            }
+
+            apply_fixtures(&case_root, case.teardown);
+        }
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    /// System
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    #[derive(Debug)]
+    pub enum SystemTrashError {
+        NoTrashProgram,
+        Other(Error),
+    }
+    impl fmt::Display for SystemTrashError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "SystemTrashError during a `trash` operation: {:?}", self)
+        }
+    }
+    impl std::error::Error for SystemTrashError {}
+
+    fn is_program_in_path(program: &str) -> bool {
+        if let Some(path_vars) = std::env::var_os("PATH") {
+            for path in std::env::split_paths(&path_vars) {
+                let full_path = path.join(program);
+                if full_path.is_file() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// This is based on the electron library's implementation.
+    /// See: https://github.com/electron/electron/blob/34c4c8d5088fa183f56baea28809de6f2a427e02/shell/common/platform_util_linux.cc#L96
+    pub fn delete_all_canonicalized_using_system_program(full_paths: Vec<PathBuf>) -> Result<(), SystemTrashError> {
+        static DEFAULT_TRASH: &str = "gio";
+        let trash = {
+            // Determine desktop environment and set accordingly.
+            let desktop_env = get_desktop_environment();
+            if desktop_env == DesktopEnvironment::Kde4 || desktop_env == DesktopEnvironment::Kde5 {
+                "kioclient5"
+            } else if desktop_env == DesktopEnvironment::Kde3 {
+                "kioclient"
+            } else {
+                DEFAULT_TRASH
+            }
+        };
+
+        let mut argv = Vec::<OsString>::with_capacity(full_paths.len() + 2);
+
+        if trash == "kioclient5" || trash == "kioclient" {
+            //argv.push(trash.into());
+            argv.push("move".into());
+            for full_path in &full_paths {
+                argv.push(full_path.into());
+            }
+            argv.push("trash:/".into());
+        } else {
+            //argv.push_back(ELECTRON_DEFAULT_TRASH);
+            argv.push("trash".into());
+            for full_path in &full_paths {
+                argv.push(full_path.into());
+            }
+        }
+        if !is_program_in_path(trash) {
+            return Err(SystemTrashError::NoTrashProgram);
+        }
+        // Execute command
+        let mut command = Command::new(trash);
+        command.args(argv);
+        let result = command.output().map_err(|e| {
+            SystemTrashError::Other(Error::Unknown {
+                description: format!("Tried executing: {:?} - Error was: {}", command, e),
+            })
+        })?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(SystemTrashError::Other(Error::Unknown {
+                description: format!("Used '{}', stderr: {}", trash, stderr),
+            }));
+        }
+        Ok(())
+    }
+
+    pub fn delete_all_using_system_program<I, T>(paths: I) -> Result<(), SystemTrashError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<Path>,
+    {
+        let full_paths = canonicalize_paths(paths).map_err(SystemTrashError::Other)?;
+        delete_all_canonicalized_using_system_program(full_paths)
+    }
+
+    #[derive(PartialEq)]
+    enum DesktopEnvironment {
+        Other,
+        Cinnamon,
+        Gnome,
+        // KDE3, KDE4 and KDE5 are sufficiently different that we count
+        // them as different desktop environments here.
+        Kde3,
+        Kde4,
+        Kde5,
+        Pantheon,
+        Unity,
+        Xfce,
+    }
+
+    fn env_has_var(name: &str) -> bool {
+        env::var_os(name).is_some()
+    }
+
+    /// See: https://chromium.googlesource.com/chromium/src/+/dd407d416fa941c04e33d81f2b1d8cab8196b633/base/nix/xdg_util.cc#57
+    fn get_desktop_environment() -> DesktopEnvironment {
+        static KDE_SESSION_ENV_VAR: &str = "KDE_SESSION_VERSION";
+        // XDG_CURRENT_DESKTOP is the newest standard circa 2012.
+        if let Ok(xdg_current_desktop) = env::var("XDG_CURRENT_DESKTOP") {
+            // It could have multiple values separated by colon in priority order.
+            for value in xdg_current_desktop.split(':') {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                match value {
+                    "Unity" => {
+                        // gnome-fallback sessions set XDG_CURRENT_DESKTOP to Unity
+                        // DESKTOP_SESSION can be gnome-fallback or gnome-fallback-compiz
+                        if let Ok(desktop_session) = env::var("DESKTOP_SESSION") {
+                            if desktop_session.contains("gnome-fallback") {
+                                return DesktopEnvironment::Gnome;
+                            }
+                        }
+                        return DesktopEnvironment::Unity;
+                    }
+                    "GNOME" => {
+                        return DesktopEnvironment::Gnome;
+                    }
+                    "X-Cinnamon" => {
+                        return DesktopEnvironment::Cinnamon;
+                    }
+                    "KDE" => {
+                        if let Ok(kde_session) = env::var(KDE_SESSION_ENV_VAR) {
+                            if kde_session == "5" {
+                                return DesktopEnvironment::Kde5;
+                            }
+                        }
+                        return DesktopEnvironment::Kde4;
+                    }
+                    "Pantheon" => {
+                        return DesktopEnvironment::Pantheon;
+                    }
+                    "XFCE" => {
+                        return DesktopEnvironment::Xfce;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // DESKTOP_SESSION was what everyone  used in 2010.
+        if let Ok(desktop_session) = env::var("DESKTOP_SESSION") {
+            match desktop_session.as_str() {
+                "gnome" | "mate" => {
+                    return DesktopEnvironment::Gnome;
+                }
+                "kde4" | "kde-plasma" => {
+                    return DesktopEnvironment::Kde4;
+                }
+                "kde" => {
+                    // This may mean KDE4 on newer systems, so we have to check.
+                    if env_has_var(KDE_SESSION_ENV_VAR) {
+                        return DesktopEnvironment::Kde4;
+                    }
+                    return DesktopEnvironment::Kde3;
+                }
+                "xubuntu" => {
+                    return DesktopEnvironment::Xfce;
+                }
+                _ => {}
+            }
+            if desktop_session.contains("xfce") {
+                return DesktopEnvironment::Xfce;
+            }
+        }
+
+        // Fall back on some older environment variables.
+        // Useful particularly in the DESKTOP_SESSION=default case.
+        if env_has_var("GNOME_DESKTOP_SESSION_ID") {
+            return DesktopEnvironment::Gnome;
+        } else if env_has_var("KDE_FULL_SESSION") {
+            if env_has_var(KDE_SESSION_ENV_VAR) {
+                return DesktopEnvironment::Kde4;
+            }
+            return DesktopEnvironment::Kde3;
+        }
+
+        DesktopEnvironment::Other
+    }
+}
+
+fn fs_error(path: impl Into<PathBuf>, source: std::io::Error) -> Error {
+    if is_cancelled_error(&source) {
+        return Error::Cancelled;
+    }
+    if is_partial_source_removal(&source) {
+        let inner = source.into_inner().and_then(|inner| inner.downcast::<PartialSourceRemoval>().ok());
+        let PartialSourceRemoval { copy, source } = *inner.expect("checked by is_partial_source_removal");
+        return Error::PartialSourceRemoval { path: path.into(), copy, source, items: Vec::new() };
+    }
+    Error::FileSystem { path: path.into(), source }
+}
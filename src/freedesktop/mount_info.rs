@@ -0,0 +1,153 @@
+//! Parsers for the mount tables of Linux, which are read directly instead of going through
+//! `libc::getmntent`, as the latter isn't thread-safe.
+
+use std::{ffi::OsString, os::unix::ffi::OsStringExt, path::PathBuf};
+
+use log::debug;
+
+use super::MountPoint;
+
+/// Parses the content of `/proc/self/mountinfo`, skipping lines that are malformed.
+///
+/// Each line looks like `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`,
+/// which is the mount ID, the ID of the parent mount, the device, the root of the mount within its
+/// file system, the mount point, the mount options, any amount of optional fields terminated by `-`, the
+/// file system type, the mount source and the super block options.
+pub(super) fn parse_mountinfo(content: &[u8]) -> Vec<MountPoint> {
+    content.split(|b| *b == b'\n').filter(|line| !line.is_empty()).filter_map(parse_mountinfo_line).collect()
+}
+
+fn parse_mountinfo_line(line: &[u8]) -> Option<MountPoint> {
+    let mut fields = line.split(|b| *b == b' ');
+    let _mount_id: u32 = parse_number(fields.next()?)?;
+    let _parent_id: u32 = parse_number(fields.next()?)?;
+    let (major, minor) = split_once(fields.next()?, b':')?;
+    let _device: (u32, u32) = (parse_number(major)?, parse_number(minor)?);
+    let root = unescape(fields.next()?);
+    let mount_point = unescape(fields.next()?);
+    let _mount_options = fields.next()?;
+    // Optional fields, like `shared:1`, up to the separator.
+    fields.by_ref().find(|field| *field == b"-")?;
+    let fs_type = unescape(fields.next()?);
+    let source = unescape(fields.next()?);
+
+    if !root.starts_with(b"/") || !mount_point.starts_with(b"/") {
+        debug!("Skipping a mount with a path that's not absolute: {:?}", String::from_utf8_lossy(line));
+        return None;
+    }
+    Some(MountPoint {
+        mnt_dir: PathBuf::from(OsString::from_vec(mount_point)),
+        _mnt_type: String::from_utf8_lossy(&fs_type).into(),
+        _mnt_fsname: String::from_utf8_lossy(&source).into(),
+    })
+}
+
+/// Parses the content of `/proc/mounts` or `/etc/mtab`, in the format of `fstab(5)`, skipping lines that are
+/// malformed or comments.
+pub(super) fn parse_mounts(content: &[u8]) -> Vec<MountPoint> {
+    content
+        .split(|b| *b == b'\n')
+        .filter_map(|line| {
+            let mut fields = line.split(|b| *b == b' ' || *b == b'\t').filter(|field| !field.is_empty());
+            let source = fields.next().filter(|source| !source.starts_with(b"#"))?;
+            let mount_point = unescape(fields.next()?);
+            let fs_type = unescape(fields.next()?);
+            Some(MountPoint {
+                mnt_dir: PathBuf::from(OsString::from_vec(mount_point)),
+                _mnt_type: String::from_utf8_lossy(&fs_type).into(),
+                _mnt_fsname: String::from_utf8_lossy(&unescape(source)).into(),
+            })
+        })
+        .collect()
+}
+
+fn parse_number(field: &[u8]) -> Option<u32> {
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+fn split_once(field: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let pos = field.iter().position(|b| *b == separator)?;
+    Some((&field[..pos], &field[pos + 1..]))
+}
+
+/// Undoes the escaping of the kernel, which writes space, tab, newline and backslash as octal
+/// escapes like `\040`. Backslashes that don't start a valid escape are kept as they are.
+fn unescape(field: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let escaped = field
+            .get(i + 1..i + 4)
+            .filter(|digits| field[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)))
+            .map(|digits| digits.iter().fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0')))
+            .and_then(|value| u8::try_from(value).ok());
+        match escaped {
+            Some(byte) => {
+                result.push(byte);
+                i += 4;
+            }
+            None => {
+                result.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_mountinfo, parse_mounts, unescape};
+
+    #[test]
+    fn mountinfo() {
+        let content = b"\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+36 22 98:0 /mnt1 /mnt/with\\040space rw,noatime master:1 shared:2 - ext3 /dev/root rw,errors=continue
+37 22 0:5 / /no/optional/fields rw - tmpfs tmpfs rw
+38 22 0:5 / /empty/source rw - tmpfs  rw
+not a mount
+39 22 0:5 / relative rw - tmpfs tmpfs rw
+40 22 0:5 / /missing/separator rw tmpfs tmpfs rw
+";
+        let mounts = parse_mountinfo(content);
+        let dirs: Vec<_> = mounts.iter().map(|m| m.mnt_dir.as_path()).collect();
+        assert_eq!(
+            dirs,
+            [
+                Path::new("/"),
+                Path::new("/mnt/with space"),
+                Path::new("/no/optional/fields"),
+                Path::new("/empty/source")
+            ]
+        );
+        assert_eq!(mounts[1]._mnt_type, "ext3");
+        assert_eq!(mounts[1]._mnt_fsname, "/dev/root");
+        assert_eq!(mounts[3]._mnt_fsname, "");
+    }
+
+    #[test]
+    fn mounts() {
+        let content = b"\
+# a comment
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /mnt/tab\\011and\\134backslash tmpfs rw 0 0
+
+incomplete
+";
+        let dirs: Vec<_> = parse_mounts(content).into_iter().map(|m| m.mnt_dir).collect();
+        assert_eq!(dirs, [Path::new("/"), Path::new("/mnt/tab\tand\\backslash")]);
+    }
+
+    #[test]
+    fn unescape_octal() {
+        assert_eq!(unescape(b"a\\040b\\012c"), b"a b\nc");
+        assert_eq!(unescape(b"\\\\134"), b"\\\\");
+        assert_eq!(unescape(b"trailing\\04"), b"trailing\\04");
+        assert_eq!(unescape(b"not\\089octal"), b"not\\089octal");
+        assert_eq!(unescape(b"too\\777large"), b"too\\777large");
+        assert_eq!(unescape("ünïcode\\040".as_bytes()), "ünïcode ".as_bytes());
+    }
+}
@@ -0,0 +1,216 @@
+//! Progress reporting and cancellation of trash operations.
+
+use std::{
+    fmt,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// The progress of a trash operation on a single item, as passed to a [`ProgressObserver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    /// The item that is processed, which is the path passed to `delete_all`, the original path of a
+    /// [`TrashItem`](crate::TrashItem) that's purged or restored, or the path of an entry of a trash folder
+    /// that's emptied.
+    pub item: &'a Path,
+    /// The number of files, directories and other entries of the item that were copied or removed so far.
+    ///
+    /// Items that can be renamed, as opposed to copied across file systems, are done at once without
+    /// reporting their entries.
+    pub entries: u64,
+    /// The size of the files among `entries`, in bytes.
+    pub bytes: u64,
+    /// Whether the item is done. This is the last report for the item.
+    pub done: bool,
+}
+
+/// Receives the [`Progress`] of the trash operations of a [`TrashContext`](crate::TrashContext), see
+/// [`TrashContextExtFreedesktop::set_progress_observer`](super::TrashContextExtFreedesktop::set_progress_observer).
+///
+/// It's called on the thread that runs the operation, between files, so it should return quickly.
+/// It's implemented for closures.
+pub trait ProgressObserver: Send + Sync {
+    fn on_progress(&self, progress: &Progress<'_>);
+}
+
+impl<F: Fn(&Progress<'_>) + Send + Sync> ProgressObserver for F {
+    fn on_progress(&self, progress: &Progress<'_>) {
+        self(progress)
+    }
+}
+
+/// A shared flag to cancel the trash operations of a [`TrashContext`](crate::TrashContext), see
+/// [`TrashContextExtFreedesktop::set_cancellation_token`](super::TrashContextExtFreedesktop::set_cancellation_token).
+///
+/// Clones share the flag, so one clone can be given to the context while another is cancelled from a different thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operations that use this token. This can't be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A [`ProgressObserver`] as stored in the platform trash context.
+#[derive(Clone)]
+pub(super) struct SharedObserver(pub(super) Arc<dyn ProgressObserver>);
+
+impl fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressObserver")
+    }
+}
+
+/// Tracks the progress of a single item, reports it to the observer, and checks for cancellation.
+pub(super) struct ItemProgress<'a> {
+    observer: Option<&'a dyn ProgressObserver>,
+    cancellation: Option<&'a CancellationToken>,
+    item: &'a Path,
+    entries: u64,
+    bytes: u64,
+}
+
+impl<'a> ItemProgress<'a> {
+    pub(super) fn new(
+        observer: Option<&'a dyn ProgressObserver>,
+        cancellation: Option<&'a CancellationToken>,
+        item: &'a Path,
+    ) -> Self {
+        Self { observer, cancellation, item, entries: 0, bytes: 0 }
+    }
+
+    /// Progress that's neither observed nor can be cancelled.
+    pub(super) fn untracked(item: &'a Path) -> Self {
+        Self::new(None, None, item)
+    }
+
+    /// Progress of the same item that can be cancelled the same way, but isn't reported, for work whose entries
+    /// were already reported.
+    pub(super) fn unobserved(&self) -> ItemProgress<'a> {
+        Self::new(None, self.cancellation, self.item)
+    }
+
+    /// Fails with an error that [`fs_error`](super::fs_error) turns into [`Error::Cancelled`](crate::Error::Cancelled)
+    /// if the operation was cancelled.
+    pub(super) fn check_cancelled(&self) -> std::io::Result<()> {
+        if self.cancellation.is_some_and(CancellationToken::is_cancelled) {
+            return Err(cancelled_error());
+        }
+        Ok(())
+    }
+
+    /// Records that an entry with `bytes` of content was processed.
+    pub(super) fn entry_done(&mut self, bytes: u64) {
+        self.entries += 1;
+        self.bytes += bytes;
+        self.report(false);
+    }
+
+    /// Records that the item is done.
+    pub(super) fn done(&self) {
+        self.report(true);
+    }
+
+    fn report(&self, done: bool) {
+        if let Some(observer) = self.observer {
+            observer.on_progress(&Progress { item: self.item, entries: self.entries, bytes: self.bytes, done });
+        }
+    }
+}
+
+/// Carried by the `std::io::Error` of a cancelled operation.
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the operation was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+fn cancelled_error() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, Cancelled)
+}
+
+pub(super) fn is_cancelled_error(error: &std::io::Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<Cancelled>())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        sync::{Arc, Mutex},
+    };
+
+    use super::{CancellationToken, Progress};
+    use crate::freedesktop::TrashContextExtFreedesktop;
+    use crate::{freedesktop::tests::isolated_context, Error};
+
+    #[test]
+    fn progress_and_cancellation() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        let dir = root.join("dir");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for (name, content) in [("a", "1"), ("b", "22"), ("sub/c", "333")] {
+            fs::write(dir.join(name), content).unwrap();
+        }
+
+        let token = CancellationToken::new();
+        ctx.set_cancellation_token(Some(token.clone()));
+        token.cancel();
+        assert!(matches!(ctx.delete(&dir), Err(Error::Cancelled)));
+        assert!(dir.is_dir());
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let observed = reports.clone();
+        ctx.set_progress_observer(Some(Arc::new(move |progress: &Progress<'_>| {
+            observed.lock().unwrap().push((progress.entries, progress.bytes, progress.done));
+        })));
+        ctx.set_cancellation_token(None);
+        ctx.delete(&dir).unwrap();
+        assert_eq!(std::mem::take(&mut *reports.lock().unwrap()), [(0, 0, true)], "renamed at once");
+
+        ctx.purge_all(ctx.list().unwrap()).unwrap();
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 6, "one report per entry, and one when done");
+        assert_eq!(reports.last(), Some(&(5, 6, true)));
+        assert!(ctx.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancelled_purge_keeps_trash_consistent() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        let dir = root.join("dir");
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a", "b", "c"] {
+            File::create_new(dir.join(name)).unwrap();
+        }
+        ctx.delete(&dir).unwrap();
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        ctx.set_cancellation_token(Some(token));
+        ctx.set_progress_observer(Some(Arc::new(move |_: &Progress<'_>| cancel.cancel())));
+        assert!(matches!(ctx.purge_all(ctx.list().unwrap()), Err(Error::Cancelled)));
+
+        let items = ctx.list().unwrap();
+        assert_eq!(items.len(), 1, "the partially purged item is still in the trash");
+        assert_eq!(fs::read_dir(root.join("Trash/files/dir")).unwrap().count(), 2);
+    }
+}
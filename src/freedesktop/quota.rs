@@ -0,0 +1,160 @@
+//! Keeping the size of trash folders under a limit by purging their oldest items.
+
+use std::{collections::HashMap, path::Path};
+
+use log::warn;
+
+use super::{is_same_path, list, DirectorySizeCache};
+use crate::{Error, TrashContext, TrashItem};
+
+/// A limit for the size of the trash, as enforced by [`enforce_quota`](crate::os_limited::enforce_quota), and
+/// optionally after every `delete_all`, see
+/// [`TrashContextExtFreedesktop::set_quota`](super::TrashContextExtFreedesktop::set_quota).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quota {
+    /// The maximum size in bytes, measured as the space the items occupy on disk.
+    pub max_bytes: u64,
+    pub scope: QuotaScope,
+}
+
+/// What a [`Quota`] applies to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum QuotaScope {
+    /// The total size of all trash folders, see [`trash_folders`](crate::os_limited::trash_folders). This is
+    /// the default.
+    #[default]
+    Total,
+    /// The size of each trash folder on its own.
+    EachTrashFolder,
+    /// The size of the given trash folder, like `~/.local/share/Trash`. Other trash folders are left alone.
+    TrashFolder(std::path::PathBuf),
+}
+
+/// Purges the oldest items until `quota` is met, except for `protected` items, and returns the purged items,
+/// oldest first. Items with an unknown deletion date count as the oldest. Items that can't be measured, like
+/// ones whose info file is dangling, and items that can't be purged are skipped.
+pub(crate) fn enforce_quota(
+    ctx: &TrashContext,
+    quota: &Quota,
+    protected: &[TrashItem],
+) -> Result<Vec<TrashItem>, Error> {
+    let mut groups: HashMap<Option<&Path>, Vec<(TrashItem, u64)>> = HashMap::new();
+    let mut caches: HashMap<&Path, DirectorySizeCache> = HashMap::new();
+    let items = list(ctx)?;
+    for item in &items {
+        let Some(trash_folder) = Path::new(&item.id).parent().and_then(Path::parent) else { continue };
+        let group = match &quota.scope {
+            QuotaScope::Total => None,
+            QuotaScope::EachTrashFolder => Some(trash_folder),
+            QuotaScope::TrashFolder(folder) if is_same_path(folder, trash_folder) => Some(trash_folder),
+            QuotaScope::TrashFolder(_) => continue,
+        };
+        let cache = caches.entry(trash_folder).or_insert_with(|| DirectorySizeCache::read(trash_folder));
+        match cache.disk_usage(Path::new(&item.id)) {
+            Ok(size) => groups.entry(group).or_default().push((item.clone(), size)),
+            Err(e) => {
+                warn!("Could not determine the size of {:?}, so it's not counted towards the quota: {}", item.id, e)
+            }
+        }
+    }
+    for (trash_folder, cache) in caches {
+        cache.write(trash_folder);
+    }
+
+    let mut purged = Vec::new();
+    for mut items in groups.into_values() {
+        let mut total: u64 = items.iter().map(|(_, size)| size).sum();
+        items.sort_by_key(|(item, _)| item.time_deleted);
+        for (item, size) in items {
+            if total <= quota.max_bytes {
+                break;
+            }
+            if protected.contains(&item) {
+                continue;
+            }
+            if let Err(e) = ctx.purge_all([&item]) {
+                warn!("Could not purge {:?} to meet the quota: {}", item.id, e);
+                continue;
+            }
+            total -= size;
+            purged.push(item);
+        }
+    }
+    purged.sort_by_key(|item| item.time_deleted);
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{Quota, QuotaScope};
+    use crate::{
+        freedesktop::{tests::isolated_context, TrashContextExtFreedesktop},
+        os_limited::metadata,
+        TrashContext,
+    };
+
+    #[test]
+    fn quota() {
+        let (_tmp, home, mut ctx) = isolated_context(&["mnt"]);
+        let topdir = home.join("mnt");
+        let content = vec![b'x'; 64 * 1024];
+        let trash = |path: PathBuf| {
+            fs::write(&path, &content).unwrap();
+            let item = ctx.delete_all_with_items([path]).unwrap().remove(0);
+            // Makes the order of the items independent of how fast the test runs.
+            let info = fs::read_to_string(&item.id).unwrap();
+            let (before, _) = info.split_once("DeletionDate=").unwrap();
+            let date = format!("2020-01-{:02}T00:00:00", ctx.list().unwrap().len());
+            fs::write(&item.id, format!("{before}DeletionDate={date}\n")).unwrap();
+            item
+        };
+        let size = metadata(&trash(home.join("a"))).unwrap().disk_usage.unwrap().bytes;
+        trash(home.join("b"));
+        trash(topdir.join("c"));
+        trash(topdir.join("d"));
+        let remaining = |ctx: &TrashContext| {
+            let mut names: Vec<_> = ctx.list().unwrap().into_iter().map(|item| item.name).collect();
+            names.sort();
+            names
+        };
+
+        let uid = unsafe { libc::getuid() };
+        let folder = QuotaScope::TrashFolder(topdir.join(format!(".Trash-{uid}")));
+        let purged = ctx.enforce_quota(&Quota { max_bytes: size, scope: folder }).unwrap();
+        assert_eq!(purged.into_iter().map(|item| item.name).collect::<Vec<_>>(), ["c"]);
+        assert_eq!(remaining(&ctx), ["a", "b", "d"]);
+
+        let purged = ctx.enforce_quota(&Quota { max_bytes: size * 2, scope: QuotaScope::Total }).unwrap();
+        assert_eq!(purged.into_iter().map(|item| item.name).collect::<Vec<_>>(), ["a"]);
+        assert!(ctx.enforce_quota(&Quota { max_bytes: size, scope: QuotaScope::EachTrashFolder }).unwrap().is_empty());
+
+        // Items that were just put into the trash are kept, even if they exceed the quota on their own.
+        ctx.set_quota(Some(Quota { max_bytes: 0, scope: QuotaScope::Total }));
+        fs::write(home.join("e"), &content).unwrap();
+        ctx.delete_all([home.join("e")]).unwrap();
+        assert_eq!(remaining(&ctx), ["e"]);
+    }
+
+    #[test]
+    fn quota_skips_what_it_cant_measure() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("dir/file"), vec![b'x'; 64 * 1024]).unwrap();
+        fs::write(root.join("file"), vec![b'x'; 64 * 1024]).unwrap();
+        let items = ctx.delete_all_with_items([root.join("dir"), root.join("file")]).unwrap();
+        fs::remove_file(root.join("Trash/directorysizes")).unwrap();
+        // A dangling info file, which would be the oldest item.
+        fs::write(root.join("Trash/info/dangling.trashinfo"), "[Trash Info]\nPath=/dangling\n").unwrap();
+
+        assert!(ctx.enforce_quota(&Quota { max_bytes: u64::MAX, scope: QuotaScope::Total }).unwrap().is_empty());
+        let cache = fs::read_to_string(root.join("Trash/directorysizes")).unwrap();
+        assert!(cache.ends_with(" dir\n"), "measured directories are added to the cache, got {cache:?}");
+
+        let purged = ctx.enforce_quota(&Quota { max_bytes: 0, scope: QuotaScope::Total }).unwrap();
+        assert_eq!(purged.len(), 2);
+        assert!(items.iter().all(|item| purged.contains(item)));
+        assert!(root.join("Trash/info/dangling.trashinfo").exists());
+    }
+}
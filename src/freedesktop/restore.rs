@@ -0,0 +1,463 @@
+//! Restoring items with a strategy for when something already exists at their original location, and restoring
+//! single entries of trashed directories.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use super::{
+    add_to_directory_sizes, cached_directory_size, disk_usage, ensure_virtually_exists, entry_of_trashed_item,
+    fs_error, is_partial_source_removal, move_items_no_replace, progress::ItemProgress, remove_from_directory_sizes,
+    restorable_file_in_trash_from_info_file, restore_item_to, FsError,
+};
+use crate::{Error, TrashContext, TrashItem};
+
+/// What [`restore_all_with_options`](crate::os_limited::restore_all_with_options) does if something already
+/// exists at the original location of an item.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreCollisionStrategy {
+    /// Stop with an [`Error::RestoreCollision`], like [`restore_all`](crate::os_limited::restore_all).
+    /// This is the default.
+    #[default]
+    Fail,
+    /// Leave the item in the trash, and continue with the next one.
+    Skip,
+    /// Put what exists at the original location into the trash, and restore the item in its place.
+    /// If the item can't be restored after all, what existed is put back, or else reported by
+    /// [`Error::RestoreReplaceFailed`].
+    Trash,
+    /// Restore the item next to what exists at the original location, under a free name like
+    /// `file (restored).txt` or `file (restored 2).txt`.
+    Rename,
+    /// If both the item and what exists at the original location are directories, move the contents of the
+    /// item into the existing directory, merging subdirectories the same way. Files that collide within them
+    /// are restored under a free name, like with [`Rename`](Self::Rename).
+    ///
+    /// Items that aren't both directories are renamed as well.
+    Merge,
+}
+
+/// Options of [`restore_all_with_options`](crate::os_limited::restore_all_with_options).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreOptions {
+    pub collision: RestoreCollisionStrategy,
+}
+
+/// What happened to an item passed to [`restore_all_with_options`](crate::os_limited::restore_all_with_options).
+#[derive(Debug)]
+pub enum RestoreOutcome {
+    /// The item was restored to its original location, which was free.
+    Restored,
+    /// The item was left in the trash, according to [`RestoreCollisionStrategy::Skip`].
+    Skipped,
+    /// What existed at the original location was put into the trash as `replaced`, and the item was restored
+    /// in its place, according to [`RestoreCollisionStrategy::Trash`].
+    Replaced { replaced: TrashItem },
+    /// The item was restored to `path`, according to [`RestoreCollisionStrategy::Rename`] or
+    /// [`RestoreCollisionStrategy::Merge`].
+    Renamed { path: PathBuf },
+    /// The contents of the item were merged into the existing directory at its original location, according
+    /// to [`RestoreCollisionStrategy::Merge`]. `renamed` are the paths that colliding files were restored to.
+    Merged { renamed: Vec<PathBuf> },
+}
+
+pub(crate) fn restore_all_with_options<I>(
+    ctx: &TrashContext,
+    items: I,
+    options: &RestoreOptions,
+) -> Result<Vec<(TrashItem, RestoreOutcome)>, Error>
+where
+    I: IntoIterator<Item = TrashItem>,
+{
+    let mut outcomes = Vec::new();
+    let mut iter = items.into_iter();
+    while let Some(item) = iter.next() {
+        let original_path = item.original_path();
+        if restore_item_to(ctx, &item, &original_path)? {
+            outcomes.push((item, RestoreOutcome::Restored));
+            continue;
+        }
+        // What was at the original location, if it was put into the trash but couldn't be put back.
+        let mut stranded = None;
+        let outcome = match options.collision {
+            RestoreCollisionStrategy::Fail => None,
+            RestoreCollisionStrategy::Skip => Some(RestoreOutcome::Skipped),
+            RestoreCollisionStrategy::Trash => replace(ctx, &item, &original_path, &mut stranded)
+                .map_err(|e| with_stranded(e, &original_path, stranded.take()))?,
+            RestoreCollisionStrategy::Rename => Some(rename(ctx, &item, &original_path)?),
+            RestoreCollisionStrategy::Merge => match merge(ctx, &item, &original_path)? {
+                Some(renamed) => Some(RestoreOutcome::Merged { renamed }),
+                None => Some(rename(ctx, &item, &original_path)?),
+            },
+        };
+        match outcome {
+            Some(outcome) => outcomes.push((item, outcome)),
+            None => {
+                let remaining: Vec<_> = std::iter::once(item).chain(iter).collect();
+                let error = Error::RestoreCollision { path: original_path.clone(), remaining_items: remaining };
+                return Err(with_stranded(error, &original_path, stranded));
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Puts what exists at `dest` into the trash and restores `item` in its place. Returns `None` if `dest` was
+/// taken again in the meantime. If `item` isn't restored, what existed at `dest` is put back, or else stored in
+/// `stranded`.
+fn replace(
+    ctx: &TrashContext,
+    item: &TrashItem,
+    dest: &Path,
+    stranded: &mut Option<TrashItem>,
+) -> Result<Option<RestoreOutcome>, Error> {
+    let mut replaced = Vec::new();
+    // This isn't recorded in the journal, as undoing it would collide with the restored item.
+    ctx.move_all_to_trash(crate::canonicalize_paths([dest])?, &mut replaced)?;
+    let replaced = replaced.pop().expect("an item is put into the trash or an error is returned");
+    let result = match restore_item_to(ctx, item, dest) {
+        Ok(true) => return Ok(Some(RestoreOutcome::Replaced { replaced })),
+        Ok(false) => Ok(None),
+        Err(e) => Err(e),
+    };
+    // Putting it back undoes part of the operation, so it isn't stopped by a cancellation.
+    let mut ctx = ctx.clone();
+    ctx.platform_specific.cancellation_token = None;
+    match restore_item_to(&ctx, &replaced, dest) {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("Could not put {:?} back from the trash, as the path was taken again", dest);
+            *stranded = Some(replaced);
+        }
+        Err(put_back) => {
+            warn!("Could not put {:?} back from the trash: {}", dest, put_back);
+            *stranded = Some(replaced);
+        }
+    }
+    result
+}
+
+/// Wraps `error` into an [`Error::RestoreReplaceFailed`] if what existed at `path` was left in the trash as
+/// `stranded`.
+fn with_stranded(error: Error, path: &Path, stranded: Option<TrashItem>) -> Error {
+    match stranded {
+        Some(replaced) => Error::RestoreReplaceFailed { path: path.to_owned(), replaced, source: Box::new(error) },
+        None => error,
+    }
+}
+
+/// Restores `item` under the first free name next to `dest`.
+fn rename(ctx: &TrashContext, item: &TrashItem, dest: &Path) -> Result<RestoreOutcome, Error> {
+    let (parent, name) = (dest.parent().unwrap_or(Path::new("/")), dest.file_name().unwrap_or_default());
+    for n in 1.. {
+        let path = parent.join(restored_name(name, n));
+        if restore_item_to(ctx, item, &path)? {
+            return Ok(RestoreOutcome::Renamed { path });
+        }
+    }
+    unreachable!("there's a free name before running out of numbers")
+}
+
+/// Merges the contents of `item` into the directory `dest`, and returns the paths that colliding files were
+/// restored to. Returns `None` without changing anything unless both are directories.
+fn merge(ctx: &TrashContext, item: &TrashItem, dest: &Path) -> Result<Option<Vec<PathBuf>>, Error> {
+    let original_path = item.original_path();
+    let mut progress = ctx.platform_specific.item_progress(&original_path);
+    progress.check_cancelled().map_err(|e| fs_error(&original_path, e))?;
+    let info_file = &item.id;
+    let file = restorable_file_in_trash_from_info_file(info_file);
+    ensure_virtually_exists(&file)?;
+    let is_dir = |path: &Path| path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    if !is_dir(&file) || !is_dir(dest) {
+        return Ok(None);
+    }
+    let mut renamed = Vec::new();
+    let merge = merge_dir(&file, dest, &mut renamed, &mut progress);
+    // Even a partially merged directory has a different size.
+    remove_from_directory_sizes(&file);
+    merge.map_err(|(p, e)| fs_error(p, e))?;
+    fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))?;
+    progress.done();
+    Ok(Some(renamed))
+}
+
+/// Moves the entries of `src` into `dst` and removes `src` afterwards. If the item stops with an error, the
+/// entries that were moved so far stay in `dst`, and the rest in `src`.
+fn merge_dir(
+    src: &Path,
+    dst: &Path,
+    renamed: &mut Vec<PathBuf>,
+    progress: &mut ItemProgress<'_>,
+) -> Result<(), FsError> {
+    for entry in fs::read_dir(src).map_err(|e| (src.to_owned(), e))? {
+        let entry = entry.map_err(|e| (src.to_owned(), e))?;
+        let (entry_src, name) = (entry.path(), entry.file_name());
+        progress.check_cancelled().map_err(|e| (entry_src.clone(), e))?;
+        let entry_dst = dst.join(&name);
+        let both_dirs = entry.file_type().map_err(|e| (entry_src.clone(), e))?.is_dir()
+            && entry_dst.symlink_metadata().is_ok_and(|m| m.is_dir());
+        if both_dirs {
+            merge_dir(&entry_src, &entry_dst, renamed, progress)?;
+            continue;
+        }
+        let mut n = 0;
+        loop {
+            let path = if n == 0 { entry_dst.clone() } else { dst.join(restored_name(&name, n)) };
+            match move_items_no_replace(&entry_src, &path, progress) {
+                Ok(()) if n == 0 => break,
+                Ok(()) => {
+                    renamed.push(path);
+                    break;
+                }
+                Err((p, e)) if e.kind() == ErrorKind::AlreadyExists && p == path => n += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    fs::remove_dir(src).map_err(|e| (src.to_owned(), e))
+}
+
+pub(crate) fn restore_sub_path(ctx: &TrashContext, item: &TrashItem, sub_path: &Path) -> Result<PathBuf, Error> {
+    if sub_path.file_name().is_none() {
+        let e = std::io::Error::new(ErrorKind::InvalidInput, "the sub-path must name an entry within the item");
+        return Err(fs_error(sub_path, e));
+    }
+    let dest = item.original_path().join(sub_path);
+    let mut progress = ctx.platform_specific.item_progress(&dest);
+    progress.check_cancelled().map_err(|e| fs_error(&dest, e))?;
+    let info_file = &item.id;
+    let file = restorable_file_in_trash_from_info_file(info_file);
+    ensure_virtually_exists(&file)?;
+    let (src, _) = entry_of_trashed_item(&file, sub_path)?;
+
+    let cached_size = cached_directory_size(&file, Path::new(info_file));
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| fs_error(parent, e))?;
+    }
+    let moved = move_items_no_replace(&src, &dest, &mut progress);
+    if moved.is_ok() || moved.as_ref().is_err_and(|(_, e)| is_partial_source_removal(e)) {
+        // The item stays in the trash, so a cached size is updated instead of removed.
+        match (cached_size, disk_usage(&file)) {
+            (Some(_), Ok(usage)) => add_to_directory_sizes(&file, Path::new(info_file), usage.bytes),
+            (Some(_), Err(_)) => remove_from_directory_sizes(&file),
+            (None, _) => {}
+        }
+    }
+    match moved {
+        Ok(()) => {}
+        Err((p, e)) if e.kind() == ErrorKind::AlreadyExists && p == dest => {
+            return Err(Error::RestoreCollision { path: dest, remaining_items: vec![item.clone()] });
+        }
+        Err((p, e)) => return Err(fs_error(p, e)),
+    }
+    progress.done();
+    Ok(dest)
+}
+
+/// Returns the `n`th free name to restore an item named `name` under, like `file (restored).txt` for 1 and
+/// `file (restored 2).txt` for 2. The extension of hidden files without another dot, like `.bashrc`, is kept
+/// as part of their name.
+fn restored_name(name: &OsStr, n: u32) -> OsString {
+    let path = Path::new(name);
+    let mut restored = path.file_stem().unwrap_or(name).to_owned();
+    if n == 1 {
+        restored.push(" (restored)");
+    } else {
+        restored.push(format!(" (restored {n})"));
+    }
+    if let Some(extension) = path.extension() {
+        restored.push(".");
+        restored.push(extension);
+    }
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs, io::Write, path::Path, sync::Arc};
+
+    use super::{restored_name, RestoreCollisionStrategy, RestoreOptions, RestoreOutcome};
+    use crate::{
+        freedesktop::{
+            cached_directory_size, tests::isolated_context, CancellationToken, Progress, TrashContextExtFreedesktop,
+        },
+        os_limited::metadata,
+        Error,
+    };
+
+    #[test]
+    fn restored_names() {
+        assert_eq!(restored_name(OsStr::new("file.txt"), 1), "file (restored).txt");
+        assert_eq!(restored_name(OsStr::new("file.txt"), 2), "file (restored 2).txt");
+        assert_eq!(restored_name(OsStr::new("archive.tar.gz"), 1), "archive.tar (restored).gz");
+        assert_eq!(restored_name(OsStr::new("folder"), 3), "folder (restored 3)");
+        assert_eq!(restored_name(OsStr::new(".bashrc"), 1), ".bashrc (restored)");
+    }
+
+    #[test]
+    fn restore_collision_strategies() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        let write = |name: &str, content: &str| {
+            let path = root.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        let trash_and_replace = |names: &[&str], content: &str| {
+            let items = ctx.delete_all_with_items(names.iter().map(|name| root.join(name))).unwrap();
+            names.iter().for_each(|name| write(name, content));
+            items
+        };
+        let restore = |items, collision| {
+            let options = RestoreOptions { collision };
+            ctx.restore_all_with_options(items, &options).unwrap().into_iter().map(|(_, outcome)| outcome)
+        };
+        let read = |name: &str| fs::read_to_string(root.join(name)).unwrap();
+
+        write("free", "trashed");
+        write("file.txt", "trashed");
+        let free = ctx.delete_all_with_items([root.join("free")]).unwrap();
+        let items = trash_and_replace(&["file.txt"], "new");
+        match ctx.restore_all_with_options(free.into_iter().chain(items.clone()), &RestoreOptions::default()) {
+            Err(Error::RestoreCollision { path, remaining_items }) => {
+                assert_eq!(path, root.join("file.txt"));
+                assert_eq!(remaining_items, items);
+            }
+            other => panic!("expected a collision, got {other:?}"),
+        }
+        assert_eq!(read("free"), "trashed");
+
+        let outcomes: Vec<_> = restore(items.clone(), RestoreCollisionStrategy::Skip).collect();
+        assert!(matches!(outcomes[..], [RestoreOutcome::Skipped]));
+        assert_eq!(read("file.txt"), "new");
+
+        match restore(items, RestoreCollisionStrategy::Trash).next() {
+            Some(RestoreOutcome::Replaced { replaced }) => {
+                assert_eq!(read("file.txt"), "trashed");
+                assert_eq!(ctx.list().unwrap(), [replaced]);
+            }
+            other => panic!("expected a replacement, got {other:?}"),
+        }
+        ctx.purge_all(ctx.list().unwrap()).unwrap();
+
+        let items = trash_and_replace(&["file.txt"], "new");
+        write("file (restored).txt", "taken");
+        let outcomes: Vec<_> = restore(items, RestoreCollisionStrategy::Rename).collect();
+        assert!(
+            matches!(&outcomes[..], [RestoreOutcome::Renamed { path }] if *path == root.join("file (restored 2).txt"))
+        );
+        assert_eq!(read("file (restored 2).txt"), "trashed");
+
+        write("dir/same", "trashed");
+        write("dir/only-trashed", "trashed");
+        write("dir/sub/nested", "trashed");
+        write("not-a-dir", "trashed");
+        let items = ctx.delete_all_with_items([root.join("dir"), root.join("not-a-dir")]).unwrap();
+        write("dir/same", "new");
+        write("dir/sub/only-new", "new");
+        write("not-a-dir", "new");
+        let outcomes: Vec<_> = restore(items, RestoreCollisionStrategy::Merge).collect();
+        match &outcomes[..] {
+            [RestoreOutcome::Merged { renamed }, RestoreOutcome::Renamed { path }] => {
+                assert_eq!(renamed, &[root.join("dir/same (restored)")]);
+                assert_eq!(path, &root.join("not-a-dir (restored)"));
+            }
+            other => panic!("expected a merge and a rename, got {other:?}"),
+        }
+        assert_eq!(read("dir/same"), "new");
+        assert_eq!(read("dir/same (restored)"), "trashed");
+        assert_eq!(read("dir/only-trashed"), "trashed");
+        assert_eq!(read("dir/sub/nested"), "trashed");
+        assert_eq!(read("dir/sub/only-new"), "new");
+        assert!(ctx.list().unwrap().is_empty());
+        assert!(fs::read_dir(root.join("Trash/files")).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn failed_replacement_puts_back_what_was_there() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        fs::write(root.join("file"), "trashed").unwrap();
+        let items = ctx.delete_all_with_items([root.join("file")]).unwrap();
+        fs::write(root.join("file"), "new").unwrap();
+
+        // Cancels once what's at the original location was put into the trash.
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        ctx.set_cancellation_token(Some(token));
+        ctx.set_progress_observer(Some(Arc::new(move |progress: &Progress<'_>| {
+            if progress.done {
+                cancel.cancel();
+            }
+        })));
+        let options = RestoreOptions { collision: RestoreCollisionStrategy::Trash };
+        assert!(matches!(ctx.restore_all_with_options(items.clone(), &options), Err(Error::Cancelled)));
+        assert_eq!(fs::read_to_string(root.join("file")).unwrap(), "new");
+        assert_eq!(ctx.list().unwrap(), items, "only the item that was to be restored is in the trash");
+    }
+
+    #[test]
+    fn replacement_collision_reports_what_was_trashed() {
+        let (_tmp, root, mut ctx) = isolated_context(&[]);
+        let path = root.join("file");
+        fs::write(&path, "trashed").unwrap();
+        let items = ctx.delete_all_with_items([&path]).unwrap();
+        fs::write(&path, "existing").unwrap();
+
+        // Something else takes the original location once what was there is put into the trash.
+        let taken = path.clone();
+        ctx.set_progress_observer(Some(Arc::new(move |progress: &Progress<'_>| {
+            if progress.done {
+                let _ = fs::File::create_new(&taken).and_then(|mut file| file.write_all(b"taken"));
+            }
+        })));
+        let options = RestoreOptions { collision: RestoreCollisionStrategy::Trash };
+        let replaced = match ctx.restore_all_with_options(items.clone(), &options) {
+            Err(Error::RestoreReplaceFailed { path: failed, replaced, source }) if failed == path => {
+                assert!(matches!(*source, Error::RestoreCollision { remaining_items, .. } if remaining_items == items));
+                replaced
+            }
+            other => panic!("unexpected result {other:?}"),
+        };
+        assert_eq!(fs::read_to_string(&path).unwrap(), "taken");
+        let mut in_trash = ctx.list().unwrap();
+        in_trash.sort_by_key(|item| item.id.clone());
+        assert_eq!(in_trash, [replaced, items[0].clone()], "both are in the trash");
+    }
+
+    #[test]
+    fn restore_sub_path() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        fs::create_dir_all(root.join("project/src")).unwrap();
+        fs::write(root.join("project/src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("project/README"), "read me").unwrap();
+        std::os::unix::fs::symlink(root.join("outside"), root.join("project/link")).unwrap();
+        fs::create_dir(root.join("outside")).unwrap();
+        fs::write(root.join("outside/secret"), "secret").unwrap();
+        let item = ctx.delete_all_with_items([root.join("project")]).unwrap().remove(0);
+        let file = root.join("Trash/files/project");
+        let initial_size = metadata(&item).unwrap().disk_usage.unwrap().bytes;
+        assert!(cached_directory_size(&file, Path::new(&item.id)).is_some());
+
+        for invalid in ["../outside", "/outside", "link/secret", "README/nested"] {
+            assert!(ctx.restore_sub_path(&item, invalid).is_err(), "{invalid} is rejected");
+        }
+        assert!(root.join("outside/secret").exists());
+
+        let restored = ctx.restore_sub_path(&item, "src/main.rs").unwrap();
+        assert_eq!(restored, root.join("project/src/main.rs"));
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "fn main() {}");
+        assert!(!file.join("src/main.rs").exists());
+        assert!(Path::new(&item.id).is_file());
+        assert_eq!(ctx.list().unwrap(), std::slice::from_ref(&item));
+        let size = cached_directory_size(&file, Path::new(&item.id)).unwrap();
+        assert!(size < initial_size);
+
+        fs::write(root.join("project/README"), "taken").unwrap();
+        assert!(matches!(ctx.restore_sub_path(&item, "README"), Err(Error::RestoreCollision { .. })));
+        assert_eq!(fs::read_to_string(file.join("README")).unwrap(), "read me");
+    }
+}
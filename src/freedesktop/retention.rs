@@ -0,0 +1,171 @@
+//! Purging the items that have been in the trash for longer than a given duration, like `trash-empty` of
+//! trash-cli or the automatic emptying of GNOME.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{get_topdir_of_path, is_same_path, list};
+use crate::{Error, ItemError, ItemErrorKind, TrashContext, TrashItem};
+
+/// What [`purge_older_than`](crate::os_limited::purge_older_than) does with items whose deletion date is unknown,
+/// which [`list`](crate::os_limited::list) reports as a `time_deleted` of `-1`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownDeletionDate {
+    /// Keep them in the trash. This is the default.
+    #[default]
+    Keep,
+    /// Purge them as if they were older than any duration.
+    Purge,
+}
+
+/// Options of [`purge_older_than`](crate::os_limited::purge_older_than).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionOptions {
+    pub unknown_deletion_date: UnknownDeletionDate,
+    /// Only consider the items in these trash folders, like `~/.local/share/Trash` or `/mnt/usb/.Trash-1000`.
+    /// `None`, the default, considers all trash folders.
+    pub trash_folders: Option<Vec<PathBuf>>,
+    /// Only consider the items in the trash folders of these topdirs, which are the directories where file
+    /// systems are mounted, like `/mnt/usb`. The 'home trash' belongs to the topdir of the file system it's on.
+    /// `None`, the default, considers all topdirs.
+    pub topdirs: Option<Vec<PathBuf>>,
+    /// Only return the items that would be purged, and leave them in the trash.
+    pub dry_run: bool,
+}
+
+pub(crate) fn purge_older_than(
+    ctx: &TrashContext,
+    age: Duration,
+    options: &RetentionOptions,
+) -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let cutoff = i64::try_from(now.saturating_sub(age.as_secs())).unwrap_or(i64::MAX);
+    let sorted_mount_points = match options.topdirs {
+        Some(_) => ctx.platform_specific.sorted_mount_points()?,
+        None => Vec::new(),
+    };
+    let is_in_scope = |item: &TrashItem| {
+        let Some(trash_folder) = Path::new(&item.id).parent().and_then(Path::parent) else {
+            return false;
+        };
+        let in_trash_folders = options
+            .trash_folders
+            .as_ref()
+            .is_none_or(|folders| folders.iter().any(|folder| is_same_path(folder, trash_folder)));
+        let in_topdirs = options.topdirs.as_ref().is_none_or(|topdirs| {
+            let topdir = get_topdir_of_path(trash_folder, &sorted_mount_points);
+            topdirs.iter().any(|candidate| is_same_path(candidate, &topdir))
+        });
+        in_trash_folders && in_topdirs
+    };
+    let is_expired = |item: &TrashItem| match item.time_deleted {
+        -1 => options.unknown_deletion_date == UnknownDeletionDate::Purge,
+        time_deleted => time_deleted < cutoff,
+    };
+    let expired: Vec<_> = list(ctx)?.into_iter().filter(|item| is_in_scope(item) && is_expired(item)).collect();
+    if options.dry_run {
+        return Ok((expired, Vec::new()));
+    }
+    // Items are purged one by one, so that a failure doesn't keep the others from being purged.
+    let mut purged = Vec::new();
+    let mut errors = Vec::new();
+    for item in expired {
+        match ctx.purge_all([&item]) {
+            Ok(()) => purged.push(item),
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(Error::FileSystem { path, source }) => errors.push(ItemError { path, kind: ItemErrorKind::Io(source) }),
+            Err(e) => {
+                let kind = ItemErrorKind::Io(std::io::Error::other(e.to_string()));
+                errors.push(ItemError { path: item.id.into(), kind });
+            }
+        }
+    }
+    Ok((purged, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use super::{RetentionOptions, UnknownDeletionDate};
+    use crate::{freedesktop::tests::isolated_context, TrashItem};
+
+    #[test]
+    fn purge_older_than() {
+        let (_tmp, home, ctx) = isolated_context(&["mnt"]);
+        let topdir = home.join("mnt");
+        let paths = [home.join("old"), home.join("new"), home.join("unknown"), topdir.join("old")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        let items = ctx.delete_all_with_items(&paths).unwrap();
+        let set_deletion_date = |item: &TrashItem, date: Option<&str>| {
+            let info = fs::read_to_string(&item.id).unwrap();
+            let lines = info.lines().filter(|line| !line.starts_with("DeletionDate="));
+            let date = date.map(|date| format!("DeletionDate={date}"));
+            fs::write(&item.id, lines.chain(date.as_deref()).collect::<Vec<_>>().join("\n")).unwrap();
+        };
+        set_deletion_date(&items[0], Some("2000-01-01T00:00:00"));
+        set_deletion_date(&items[2], None);
+        set_deletion_date(&items[3], Some("2000-01-01T00:00:00"));
+        let original_paths = |items: Vec<TrashItem>| {
+            let mut paths: Vec<_> = items.iter().map(|item| item.original_path()).collect();
+            paths.sort();
+            paths
+        };
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+
+        let dry_run = RetentionOptions { dry_run: true, ..Default::default() };
+        let (expired, errors) = ctx.purge_older_than(day, &dry_run).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(original_paths(expired), [topdir.join("old"), home.join("old")]);
+        assert_eq!(ctx.list().unwrap().len(), 4, "a dry run doesn't purge");
+
+        let unknown = RetentionOptions { unknown_deletion_date: UnknownDeletionDate::Purge, ..dry_run.clone() };
+        let (expired, _) = ctx.purge_older_than(day, &unknown).unwrap();
+        assert_eq!(original_paths(expired), [topdir.join("old"), home.join("old"), home.join("unknown")]);
+
+        let uid = unsafe { libc::getuid() };
+        let in_topdir = RetentionOptions { topdirs: Some(vec![topdir.clone()]), ..dry_run.clone() };
+        assert_eq!(original_paths(ctx.purge_older_than(day, &in_topdir).unwrap().0), [topdir.join("old")]);
+        let in_folder = RetentionOptions { trash_folders: Some(vec![topdir.join(format!(".Trash-{uid}"))]), ..dry_run };
+        assert_eq!(original_paths(ctx.purge_older_than(day, &in_folder).unwrap().0), [topdir.join("old")]);
+
+        let (purged, errors) = ctx.purge_older_than(day, &RetentionOptions::default()).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(original_paths(purged), [topdir.join("old"), home.join("old")]);
+        assert_eq!(original_paths(ctx.list().unwrap()), [home.join("new"), home.join("unknown")]);
+        assert_eq!(
+            ctx.purge_older_than(day * 365 * 100, &unknown).unwrap().0.len(),
+            1,
+            "only the unknown date is old enough"
+        );
+    }
+
+    #[test]
+    fn purge_older_than_continues_after_failures() {
+        let (_tmp, root, ctx) = isolated_context(&[]);
+        let paths = [root.join("a"), root.join("b"), root.join("c")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        let items = ctx.delete_all_with_items(&paths).unwrap();
+        for item in &items {
+            let info = fs::read_to_string(&item.id).unwrap();
+            let (info, _) = info.split_once("DeletionDate=").unwrap();
+            fs::write(&item.id, format!("{info}DeletionDate=2000-01-01T00:00:00\n")).unwrap();
+        }
+        // The item of a dangling info file can't be purged.
+        fs::remove_file(root.join("Trash/files/b")).unwrap();
+
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        let (purged, errors) = ctx.purge_older_than(day, &RetentionOptions::default()).unwrap();
+        let mut purged: Vec<_> = purged.iter().map(|item| item.name.clone()).collect();
+        purged.sort();
+        assert_eq!(purged, ["a", "c"]);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(ctx.list().unwrap(), items[1..2], "what couldn't be purged is left");
+    }
+}
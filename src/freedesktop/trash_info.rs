@@ -0,0 +1,392 @@
+//! Reading and writing of `.trashinfo` files, see "Contents of a trash directory" in the specification.
+
+use std::{
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{decode_uri_path, encode_uri_path};
+
+/// The name of the only group a `.trashinfo` file has to contain.
+const TRASH_INFO_GROUP: &str = "Trash Info";
+
+/// The content of a `.trashinfo` file, which describes a single item in a trash folder.
+///
+/// # Example
+///
+/// ```
+/// use trash::freedesktop::TrashInfo;
+///
+/// let content = b"[Trash Info]\nPath=/home/user/New%20Folder\nDeletionDate=2004-08-31T22:32:08\n";
+/// let info = TrashInfo::parse(content).unwrap();
+/// assert_eq!(info.path, std::path::Path::new("/home/user/New Folder"));
+/// assert_eq!(info.serialize().as_bytes(), content);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashInfo {
+    /// The original location of the item, decoded from the URI-encoded `Path` key.
+    ///
+    /// It's either absolute, or relative to the top directory of the mount the trash folder resides on.
+    pub path: PathBuf,
+    /// The local time at which the item was put into the trash, if it was recorded.
+    pub deletion_date: Option<DeletionDate>,
+    /// Keys of the `[Trash Info]` group other than `Path` and `DeletionDate` along with their values,
+    /// in the order they appeared in.
+    pub unknown: Vec<(String, String)>,
+}
+
+impl TrashInfo {
+    /// Parses the content of a `.trashinfo` file.
+    ///
+    /// Blank lines and comments are ignored, as are groups other than `[Trash Info]`, which has to be
+    /// the first group of the file. If `Path` or `DeletionDate` appear more than once, the first
+    /// occurrence is used.
+    pub fn parse(content: &[u8]) -> Result<Self, TrashInfoError> {
+        let content = std::str::from_utf8(content).map_err(|e| TrashInfoError::InvalidUtf8 {
+            line: content[..e.valid_up_to()].iter().filter(|b| **b == b'\n').count() + 1,
+        })?;
+
+        // `None` until the first group header, then whether we are within the `[Trash Info]` group.
+        let mut in_trash_info_group = None;
+        let mut path = None;
+        let mut deletion_date = None;
+        let mut unknown = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(group) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                if in_trash_info_group.is_none() && group != TRASH_INFO_GROUP {
+                    return Err(TrashInfoError::MissingHeader);
+                }
+                in_trash_info_group = Some(group == TRASH_INFO_GROUP);
+                continue;
+            }
+            match in_trash_info_group {
+                None => return Err(TrashInfoError::MissingHeader),
+                Some(false) => continue,
+                Some(true) => {}
+            }
+            let (key, value) = line.split_once('=').ok_or(TrashInfoError::InvalidLine { line: index + 1 })?;
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "Path" => {
+                    if path.is_none() {
+                        path = Some(decode_uri_path(value));
+                    }
+                }
+                "DeletionDate" => {
+                    if deletion_date.is_none() {
+                        let date = value
+                            .parse()
+                            .map_err(|_| TrashInfoError::InvalidDeletionDate { value: value.to_owned() })?;
+                        deletion_date = Some(date);
+                    }
+                }
+                _ => unknown.push((key.to_owned(), value.to_owned())),
+            }
+        }
+
+        if in_trash_info_group.is_none() {
+            return Err(TrashInfoError::MissingHeader);
+        }
+        let path = path.filter(|path| !path.as_os_str().is_empty()).ok_or(TrashInfoError::MissingPath)?;
+        Ok(TrashInfo { path, deletion_date, unknown })
+    }
+
+    /// Returns the content of a `.trashinfo` file describing this item.
+    ///
+    /// The `Path` is URI-encoded like GNOME, KDE and trash-cli do it, so files written by these
+    /// round-trip through [`parse`](Self::parse) and `serialize`.
+    pub fn serialize(&self) -> String {
+        let mut content = format!("[{TRASH_INFO_GROUP}]\nPath={}\n", encode_uri_path(&self.path));
+        if let Some(date) = &self.deletion_date {
+            content.push_str(&format!("DeletionDate={date}\n"));
+        }
+        for (key, value) in &self.unknown {
+            content.push_str(&format!("{key}={value}\n"));
+        }
+        content
+    }
+}
+
+/// The local date and time stored in the `DeletionDate` key of a `.trashinfo` file,
+/// formatted as `YYYY-MM-DDThh:mm:ss`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeletionDate {
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    /// 0 to 23
+    pub hour: u8,
+    /// 0 to 59
+    pub minute: u8,
+    /// 0 to 60, to allow for leap seconds
+    pub second: u8,
+}
+
+impl DeletionDate {
+    /// The current local time, if it can be represented.
+    pub fn now() -> Option<Self> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        Self::from_unix_time(i64::try_from(now.as_secs()).ok()?)
+    }
+
+    /// Converts `time`, the number of non-leap seconds since the UNIX epoch, into the local time zone.
+    #[cfg(feature = "chrono")]
+    pub fn from_unix_time(time: i64) -> Option<Self> {
+        use chrono::{Datelike, TimeZone, Timelike};
+        let time = chrono::Local.timestamp_opt(time, 0).single()?;
+        Some(DeletionDate {
+            year: u16::try_from(time.year()).ok()?,
+            month: time.month() as u8,
+            day: time.day() as u8,
+            hour: time.hour() as u8,
+            minute: time.minute() as u8,
+            second: time.second() as u8,
+        })
+    }
+
+    /// Converts `time`, the number of non-leap seconds since the UNIX epoch, into the local time zone.
+    #[cfg(not(feature = "chrono"))]
+    pub fn from_unix_time(time: i64) -> Option<Self> {
+        let time = libc::time_t::try_from(time).ok()?;
+        // SAFETY: `tm` is plain old data, and `localtime_r` only writes to the `tm` it's given.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+            return None;
+        }
+        Some(DeletionDate {
+            year: u16::try_from(tm.tm_year + 1900).ok()?,
+            month: (tm.tm_mon + 1) as u8,
+            day: tm.tm_mday as u8,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            // `localtime_r` may report leap seconds as 60.
+            second: tm.tm_sec as u8,
+        })
+    }
+
+    /// Interprets the date in the local time zone and returns the number of non-leap seconds since
+    /// the UNIX epoch, if it can be determined.
+    ///
+    /// If the local time is ambiguous, like during the transition from daylight saving time, the
+    /// earliest of the candidates is used.
+    #[cfg(feature = "chrono")]
+    pub fn to_unix_time(self) -> Option<i64> {
+        use chrono::TimeZone;
+        let naive = chrono::NaiveDate::from_ymd_opt(self.year.into(), self.month.into(), self.day.into())?
+            .and_hms_opt(self.hour.into(), self.minute.into(), self.second.into())?;
+        chrono::Local.from_local_datetime(&naive).earliest().map(|time| time.timestamp())
+    }
+
+    /// Interprets the date in the local time zone and returns the number of non-leap seconds since
+    /// the UNIX epoch, if it can be determined.
+    ///
+    /// If the local time is ambiguous, like during the transition from daylight saving time, the
+    /// C library decides which of the candidates is used.
+    #[cfg(not(feature = "chrono"))]
+    pub fn to_unix_time(self) -> Option<i64> {
+        // SAFETY: `tm` is plain old data, and all fields `mktime` reads are initialized below.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        tm.tm_year = libc::c_int::from(self.year) - 1900;
+        tm.tm_mon = libc::c_int::from(self.month) - 1;
+        tm.tm_mday = self.day.into();
+        tm.tm_hour = self.hour.into();
+        tm.tm_min = self.minute.into();
+        tm.tm_sec = self.second.into();
+        // Let `mktime` figure out whether daylight saving time is in effect.
+        tm.tm_isdst = -1;
+        // `-1` signals an error, but is also one second before the epoch, which can't be a deletion date anyway.
+        match unsafe { libc::mktime(&mut tm) } {
+            -1 => None,
+            // `time_t` is only 32 bits wide on some platforms.
+            #[allow(clippy::useless_conversion)]
+            time => Some(time.into()),
+        }
+    }
+}
+
+impl fmt::Display for DeletionDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl FromStr for DeletionDate {
+    type Err = TrashInfoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TrashInfoError::InvalidDeletionDate { value: s.to_owned() };
+        let bytes = s.as_bytes();
+        if bytes.len() != 19 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b'T' {
+            return Err(invalid());
+        }
+        if bytes[13] != b':' || bytes[16] != b':' {
+            return Err(invalid());
+        }
+        let number = |range: std::ops::Range<usize>| -> Result<u16, TrashInfoError> {
+            let digits = &bytes[range];
+            if !digits.iter().all(u8::is_ascii_digit) {
+                return Err(invalid());
+            }
+            Ok(digits.iter().fold(0, |number, digit| number * 10 + u16::from(digit - b'0')))
+        };
+        let date = DeletionDate {
+            year: number(0..4)?,
+            month: number(5..7)? as u8,
+            day: number(8..10)? as u8,
+            hour: number(11..13)? as u8,
+            minute: number(14..16)? as u8,
+            second: number(17..19)? as u8,
+        };
+        let days_in_month = match date.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if date.year % 4 == 0 && (date.year % 100 != 0 || date.year % 400 == 0) => 29,
+            2 => 28,
+            _ => return Err(invalid()),
+        };
+        if date.day == 0 || date.day > days_in_month || date.hour > 23 || date.minute > 59 || date.second > 60 {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+/// The reason a `.trashinfo` file couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrashInfoError {
+    /// The content is not valid UTF-8, starting at the 1-based `line`.
+    InvalidUtf8 { line: usize },
+    /// The file doesn't start with the `[Trash Info]` group.
+    MissingHeader,
+    /// The 1-based `line` within the `[Trash Info]` group is neither a key-value pair, a comment or blank.
+    InvalidLine { line: usize },
+    /// The `Path` key is missing or empty.
+    MissingPath,
+    /// The `DeletionDate` key doesn't have the `YYYY-MM-DDThh:mm:ss` format, or isn't a valid date.
+    InvalidDeletionDate { value: String },
+}
+
+impl fmt::Display for TrashInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrashInfoError::InvalidUtf8 { line } => write!(f, "invalid UTF-8 in line {line}"),
+            TrashInfoError::MissingHeader => write!(f, "the file doesn't start with the [{TRASH_INFO_GROUP}] group"),
+            TrashInfoError::InvalidLine { line } => write!(f, "line {line} is not a key-value pair"),
+            TrashInfoError::MissingPath => write!(f, "the Path key is missing or empty"),
+            TrashInfoError::InvalidDeletionDate { value } => write!(f, "invalid DeletionDate '{value}'"),
+        }
+    }
+}
+
+impl std::error::Error for TrashInfoError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt, path::Path};
+
+    use super::{DeletionDate, TrashInfo, TrashInfoError};
+
+    fn date(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DeletionDate {
+        DeletionDate { year, month, day, hour, minute, second }
+    }
+
+    #[test]
+    fn roundtrip_files_of_other_implementations() {
+        // As written by GNOME (gio), KDE (kio) and trash-cli respectively.
+        let files: &[&[u8]] = &[
+            b"[Trash Info]\nPath=/home/user/Documents/report%20%281%29.odt\nDeletionDate=2023-11-04T09:15:42\n",
+            b"[Trash Info]\nPath=/home/user/caf%C3%A9\nDeletionDate=2024-02-29T23:59:59\n",
+            b"[Trash Info]\nPath=relative/to/topdir\nDeletionDate=1999-12-31T00:00:00\n",
+        ];
+        for content in files {
+            let info = TrashInfo::parse(content).unwrap();
+            assert_eq!(info.serialize().as_bytes(), *content);
+        }
+
+        let info = TrashInfo::parse(files[0]).unwrap();
+        assert_eq!(info.path, Path::new("/home/user/Documents/report (1).odt"));
+        assert_eq!(info.deletion_date, Some(date(2023, 11, 4, 9, 15, 42)));
+        assert!(info.unknown.is_empty());
+        assert_eq!(TrashInfo::parse(files[2]).unwrap().path, Path::new("relative/to/topdir"));
+    }
+
+    #[test]
+    fn parse_is_lenient_where_the_format_allows_it() {
+        let content = b"# written by hand\n\n[Trash Info]\r\nPath = /tmp/a=b \r\nX-Origin=somewhere=else\nPath=/ignored\n\n[Other Group]\nnot a key-value pair\n";
+        let info = TrashInfo::parse(content).unwrap();
+        assert_eq!(info.path, Path::new("/tmp/a=b"));
+        assert_eq!(info.deletion_date, None);
+        assert_eq!(info.unknown, [("X-Origin".to_owned(), "somewhere=else".to_owned())]);
+        assert_eq!(info.serialize(), "[Trash Info]\nPath=/tmp/a%3Db\nX-Origin=somewhere=else\n");
+    }
+
+    #[test]
+    fn roundtrip_invalid_unicode_path() {
+        let info =
+            TrashInfo { path: OsString::from_vec(b"/tmp/\xa8".to_vec()).into(), deletion_date: None, unknown: vec![] };
+        let serialized = info.serialize();
+        assert_eq!(serialized, "[Trash Info]\nPath=/tmp/%A8\n");
+        assert_eq!(TrashInfo::parse(serialized.as_bytes()).unwrap(), info);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases: &[(&[u8], TrashInfoError)] = &[
+            (b"", TrashInfoError::MissingHeader),
+            (b"Path=/tmp/a\n", TrashInfoError::MissingHeader),
+            (b"[Desktop Entry]\n[Trash Info]\nPath=/tmp/a\n", TrashInfoError::MissingHeader),
+            (b"[Trash Info]\nPath=/tmp/a\ngarbage\n", TrashInfoError::InvalidLine { line: 3 }),
+            (b"[Trash Info]\nDeletionDate=2023-11-04T09:15:42\n", TrashInfoError::MissingPath),
+            (b"[Trash Info]\nPath=\n", TrashInfoError::MissingPath),
+            (b"[Trash Info]\nPath=/tmp/\xff\n", TrashInfoError::InvalidUtf8 { line: 2 }),
+            (
+                b"[Trash Info]\nPath=/tmp/a\nDeletionDate=yesterday\n",
+                TrashInfoError::InvalidDeletionDate { value: "yesterday".into() },
+            ),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(TrashInfo::parse(content).unwrap_err(), *expected, "{:?}", String::from_utf8_lossy(content));
+        }
+    }
+
+    #[test]
+    fn deletion_date_unix_time_roundtrip() {
+        let now = DeletionDate::now().unwrap();
+        let time = now.to_unix_time().unwrap();
+        assert_eq!(DeletionDate::from_unix_time(time), Some(now));
+
+        let earlier = time - 400 * 24 * 60 * 60;
+        assert_eq!(DeletionDate::from_unix_time(earlier).unwrap().to_unix_time(), Some(earlier));
+    }
+
+    #[test]
+    fn deletion_date_validation() {
+        assert_eq!("2024-02-29T12:00:00".parse::<DeletionDate>().unwrap(), date(2024, 2, 29, 12, 0, 0));
+        for invalid in [
+            "2023-02-29T12:00:00",
+            "1900-02-29T12:00:00",
+            "2023-13-01T12:00:00",
+            "2023-04-31T12:00:00",
+            "2023-01-01T24:00:00",
+            "2023-01-01 12:00:00",
+            "2023-01-01T12:00:00Z",
+            "2023-1-01T12:00:000",
+            "+023-01-01T12:00:00",
+            "2023-01-01T12:00:é",
+        ] {
+            assert!(invalid.parse::<DeletionDate>().is_err(), "{invalid} should be rejected");
+        }
+    }
+}
@@ -4,12 +4,14 @@
 //! Furthermore on Linux and on Windows additional functions are available from the `os_limited`
 //! module.
 //!
-//! ### Potential UB on Linux and FreeBSD
+//! ### Potential UB on FreeBSD and other BSDs
 //!
-//! When querying information about mount points, non-threadsafe versions of `libc::getmnt(info|ent)` are
+//! When querying information about mount points, the non-threadsafe `libc::getmntinfo` is
 //! used which can cause UB if another thread calls into the same function, _probably_ only if the mountpoints
 //! changed as well.
 //!
+//! On Linux, the mount tables are parsed directly instead, which is thread-safe.
+//!
 //! To neutralize the issue, the respective function in this crate has been made thread-safe with a Mutex.
 //!
 //! **If your crate calls into the aforementioned methods directly or indirectly from other threads,
@@ -28,6 +30,9 @@
 //! GNOME, KDE, and XFCE all use this convention. This crate blindly assumes that the Linux
 //! distribution it runs on, follows this specification.
 //!
+//! Types that are specific to this specification, like the parser for `.trashinfo` files, are
+//! available from the `freedesktop` module.
+//!
 
 use std::ffi::OsString;
 use std::hash::{Hash, Hasher};
@@ -46,8 +51,9 @@ pub mod tests;
 mod platform;
 
 #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
-#[path = "freedesktop.rs"]
-mod platform;
+pub mod freedesktop;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+use freedesktop as platform;
 
 #[cfg(target_os = "macos")]
 pub mod macos;
@@ -59,7 +65,7 @@ pub const DEFAULT_TRASH_CTX: TrashContext = TrashContext::new();
 /// A collection of preferences for trash operations.
 #[derive(Clone, Default, Debug)]
 pub struct TrashContext {
-    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    #[cfg_attr(target_os = "windows", allow(dead_code))]
     platform_specific: platform::PlatformTrashContext,
 }
 impl TrashContext {
@@ -133,6 +139,7 @@ where
 
 /// Provides information about an error.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     Unknown {
         description: String,
@@ -152,6 +159,32 @@ pub enum Error {
         source: std::io::Error,
     },
 
+    /// **freedesktop only**
+    ///
+    /// The operation was cancelled with the [`CancellationToken`](freedesktop::CancellationToken) of its
+    /// [`TrashContext`]. Items that were done before remain done, and the trash is left in a consistent state.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    Cancelled,
+
+    /// **freedesktop only**
+    ///
+    /// An item had to be copied as it was moved across file systems, and the copy at `copy` is complete,
+    /// but the original at `path` could only be partially removed afterwards. So some of the item's
+    /// contents still exist in both places.
+    ///
+    /// When deleting, the item was put into the trash regardless, and no further items are deleted.
+    /// When restoring, the item stays in the trash with what's left of it.
+    ///
+    /// `items`: When deleting, the items that were put into the trash, including the one at `copy`, in the order
+    /// they were provided. Empty when restoring.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    PartialSourceRemoval {
+        path: PathBuf,
+        copy: PathBuf,
+        source: std::io::Error,
+        items: Vec<TrashItem>,
+    },
+
     /// One of the target items was a root folder.
     /// If a list of items are requested to be removed by a single function call (e.g. `delete_all`)
     /// and this error is returned, then it's guaranteed that none of the items is removed.
@@ -195,6 +228,21 @@ pub enum Error {
         remaining_items: Vec<TrashItem>,
     },
 
+    /// **freedesktop only**
+    ///
+    /// Restoring an item with
+    /// [`RestoreCollisionStrategy::Trash`](freedesktop::RestoreCollisionStrategy::Trash) failed after what
+    /// existed at `path` was put into the trash as `replaced`, and it couldn't be put back either, as the path
+    /// was taken again for example. So `replaced` is still in the trash.
+    ///
+    /// `source`: Why the item wasn't restored, which is an [`Error::RestoreCollision`] if `path` was taken again.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    RestoreReplaceFailed {
+        path: PathBuf,
+        replaced: TrashItem,
+        source: Box<Error>,
+    },
+
     /// This sort of error is returned when multiple items with the same `original_path` were
     /// requested to be restored. These items are referred to as twins here. If there are twins
     /// among the items, then none of the items are restored.
@@ -217,10 +265,61 @@ impl error::Error for Error {
         match self {
             #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
             Self::FileSystem { path: _, source: e } => e.source(),
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+            Self::PartialSourceRemoval { source: e, .. } => e.source(),
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+            Self::RestoreReplaceFailed { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
 }
+/// Describes an entry of the trash that could not be turned into a [`TrashItem`].
+#[derive(Debug)]
+pub struct ItemError {
+    /// The path of the offending entry, e.g. the `.trashinfo` file on Linux.
+    pub path: PathBuf,
+    /// Why the entry could not be turned into a [`TrashItem`].
+    pub kind: ItemErrorKind,
+}
+
+/// The reason behind an [`ItemError`].
+#[derive(Debug)]
+pub enum ItemErrorKind {
+    /// The entry could not be read, or not be removed while emptying or purging the trash.
+    Io(std::io::Error),
+
+    /// **freedesktop only**
+    ///
+    /// The `.trashinfo` file is malformed.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    InvalidInfo(freedesktop::TrashInfoError),
+
+    /// The original path recorded for the entry has no file name or no parent, like `/`.
+    InvalidOriginalPath(PathBuf),
+}
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ItemErrorKind::Io(e) => write!(f, "{:?} could not be read: {e}", self.path),
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+            ItemErrorKind::InvalidInfo(e) => write!(f, "{:?} is malformed: {e}", self.path),
+            ItemErrorKind::InvalidOriginalPath(original) => {
+                write!(f, "{:?} records the invalid original path {original:?}", self.path)
+            }
+        }
+    }
+}
+impl error::Error for ItemError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            ItemErrorKind::Io(e) => Some(e),
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+            ItemErrorKind::InvalidInfo(e) => Some(e),
+            ItemErrorKind::InvalidOriginalPath(_) => None,
+        }
+    }
+}
+
 pub fn into_unknown<E: std::fmt::Display>(err: E) -> Error {
     Error::Unknown { description: format!("{err}") }
 }
@@ -286,7 +385,7 @@ pub struct TrashItem {
 
     /// The number of non-leap seconds elapsed between the UNIX Epoch and the
     /// moment the file was deleted.
-    /// Without the "chrono" feature, this will be a negative number on linux only.
+    /// On Linux this is `-1` if the deletion date is missing from the item's `.trashinfo` file.
     pub time_deleted: i64,
 }
 
@@ -336,11 +435,33 @@ impl TrashItemSize {
     }
 }
 
+/// The space a [`TrashItem`] occupies on disk, including everything inside of it if it's a directory.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TrashItemDiskUsage {
+    /// The number of bytes allocated on disk, recursively.
+    ///
+    /// On Linux this is computed the way `du -B1` does it, which is also what the `directorysizes`
+    /// cache of a trash folder records.
+    pub bytes: u64,
+    /// The number of files (anything that isn't a directory) within the item, recursively.
+    ///
+    /// This is `None` if `bytes` was read from a cache which doesn't record the amount of files.
+    pub files: Option<u64>,
+}
+
 /// Metadata about a [`TrashItem`]
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[non_exhaustive]
 pub struct TrashItemMetadata {
     /// The size of the item, depending on whether or not it is a directory.
     pub size: TrashItemSize,
+    /// The recursive size of the item on disk.
+    ///
+    /// It's only available on Linux and other Freedesktop Trash compliant environments, and `None` elsewhere.
+    /// The size of a directory is taken from the `directorysizes` cache of its trash folder, so it's `None` for
+    /// directories that aren't in the cache, like ones that were put into the trash by other programs. Repairing
+    /// the trash with [`fsck`](crate::os_limited::fsck) adds them.
+    pub disk_usage: Option<TrashItemDiskUsage>,
 }
 
 #[cfg(any(
@@ -357,7 +478,256 @@ pub mod os_limited {
         hash::{Hash, Hasher},
     };
 
-    use super::{platform, Error, TrashItem, TrashItemMetadata};
+    use super::{platform, Error, ItemError, TrashContext, TrashItem, TrashItemMetadata, DEFAULT_TRASH_CTX};
+
+    impl TrashContext {
+        /// Removes all files/directories specified by the collection of paths provided as an argument,
+        /// and returns the [`TrashItem`] that each of them became, in the same order.
+        ///
+        /// See: [`delete_all_with_items`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn delete_all_with_items<I, T>(&self, paths: I) -> Result<Vec<TrashItem>, Error>
+        where
+            I: IntoIterator<Item = T>,
+            T: AsRef<std::path::Path>,
+        {
+            let full_paths = crate::canonicalize_paths(paths)?;
+            self.delete_all_canonicalized_with_items(full_paths)
+        }
+
+        /// Returns all [`TrashItem`]s that are currently in the trash.
+        ///
+        /// See: [`list`]
+        pub fn list(&self) -> Result<Vec<TrashItem>, Error> {
+            platform::list(self)
+        }
+
+        /// Returns all [`TrashItem`]s that are currently in the trash, along with an [`ItemError`] for
+        /// every entry of the trash that could not be read or is malformed.
+        ///
+        /// See: [`list_with_errors`]
+        pub fn list_with_errors(&self) -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+            platform::list_with_errors(self)
+        }
+
+        /// Returns an iterator over the [`TrashItem`]s that are currently in the trash, which reads them lazily.
+        ///
+        /// See: [`list_iter`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn list_iter(&self) -> Result<crate::freedesktop::TrashItemIter, Error> {
+            platform::list_iter(self)
+        }
+
+        /// Returns whether the trash is empty or has at least one item.
+        ///
+        /// See: [`is_empty`]
+        pub fn is_empty(&self) -> Result<bool, Error> {
+            platform::is_empty(self)
+        }
+
+        /// Returns all valid trash bins on supported Unix platforms.
+        ///
+        /// See: [`trash_folders`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn trash_folders(&self) -> Result<HashSet<std::path::PathBuf>, Error> {
+            platform::trash_folders(self)
+        }
+
+        /// Checks all trash folders for inconsistencies, and repairs them according to `repair`.
+        ///
+        /// See: [`fsck`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn fsck(
+            &self,
+            repair: &crate::freedesktop::FsckRepair,
+        ) -> Result<Vec<crate::freedesktop::FsckFinding>, Error> {
+            platform::fsck(self, repair)
+        }
+
+        /// Returns the batches of the journal of this context, oldest first.
+        ///
+        /// Fails if no journal was set with
+        /// [`set_journal`](crate::freedesktop::TrashContextExtFreedesktop::set_journal).
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn journal_batches(&self) -> Result<Vec<crate::freedesktop::JournalBatch>, Error> {
+            platform::journal_batches(self)
+        }
+
+        /// Restores the items of the journal batch with the id `batch_id` with [`restore_all`](Self::restore_all),
+        /// and returns the batch.
+        ///
+        /// Items that aren't in the trash anymore, because they were purged or restored in the meantime, are
+        /// skipped. The items that were restored are removed from the journal, even if restoring the rest failed,
+        /// so that the batch can be undone again after resolving the error, like a [`Error::RestoreCollision`].
+        ///
+        /// Fails if no journal was set, or if it has no batch with this id.
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn undo(&self, batch_id: u64) -> Result<crate::freedesktop::JournalBatch, Error> {
+            platform::undo(self, batch_id)
+        }
+
+        /// Like [`undo`](Self::undo) for the latest batch of the journal. Returns `None` if the journal is empty.
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn undo_last(&self) -> Result<Option<crate::freedesktop::JournalBatch>, Error> {
+            platform::undo_last(self)
+        }
+
+        /// Deletes all the provided [`TrashItem`]s permanently.
+        ///
+        /// See: [`purge_all`]
+        pub fn purge_all<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator,
+            <I as IntoIterator>::Item: Borrow<TrashItem>,
+        {
+            platform::purge_all(self, items)
+        }
+
+        /// Restores all the provided [`TrashItem`] to their original location.
+        ///
+        /// See: [`restore_all`]
+        pub fn restore_all<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            // Check for twins here cause that's pretty platform independent.
+            struct ItemWrapper<'a>(&'a TrashItem);
+            impl PartialEq for ItemWrapper<'_> {
+                fn eq(&self, other: &Self) -> bool {
+                    self.0.original_path() == other.0.original_path()
+                }
+            }
+            impl Eq for ItemWrapper<'_> {}
+            impl Hash for ItemWrapper<'_> {
+                fn hash<H: Hasher>(&self, state: &mut H) {
+                    self.0.original_path().hash(state);
+                }
+            }
+            let items = items.into_iter().collect::<Vec<_>>();
+            let mut item_set = HashSet::with_capacity(items.len());
+            for item in items.iter() {
+                if !item_set.insert(ItemWrapper(item)) {
+                    return Err(Error::RestoreTwins { path: item.original_path(), items });
+                }
+            }
+            platform::restore_all(self, items)
+        }
+
+        /// Removes everything from all trash folders.
+        ///
+        /// See: [`empty`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn empty(&self) -> Result<Vec<ItemError>, Error> {
+            platform::empty(self)
+        }
+
+        /// Removes everything from the trash folder at `path`.
+        ///
+        /// See: [`empty_folder`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn empty_folder(&self, path: impl AsRef<std::path::Path>) -> Result<Vec<ItemError>, Error> {
+            platform::empty_folder(self, path.as_ref())
+        }
+
+        /// Purges the oldest items until `quota` is met, and returns them.
+        ///
+        /// See: [`enforce_quota`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn enforce_quota(&self, quota: &crate::freedesktop::Quota) -> Result<Vec<TrashItem>, Error> {
+            platform::enforce_quota(self, quota, &[])
+        }
+
+        /// Purges the items that have been in the trash for longer than `age`, and returns them along with
+        /// an [`ItemError`] for every item that could not be purged.
+        ///
+        /// See: [`purge_older_than`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn purge_older_than(
+            &self,
+            age: std::time::Duration,
+            options: &crate::freedesktop::RetentionOptions,
+        ) -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+            platform::purge_older_than(self, age, options)
+        }
+
+        /// Restores all the provided [`TrashItem`]s to their original location, and handles collisions
+        /// according to `options`.
+        ///
+        /// See: [`restore_all_with_options`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn restore_all_with_options<I>(
+            &self,
+            items: I,
+            options: &crate::freedesktop::RestoreOptions,
+        ) -> Result<Vec<(TrashItem, crate::freedesktop::RestoreOutcome)>, Error>
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            platform::restore_all_with_options(self, items, options)
+        }
+
+        /// Restores the entry at `sub_path` within the provided directory [`TrashItem`] to its original location,
+        /// and leaves the rest of the directory in the trash.
+        ///
+        /// See: [`restore_sub_path`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn restore_sub_path(
+            &self,
+            item: &TrashItem,
+            sub_path: impl AsRef<std::path::Path>,
+        ) -> Result<std::path::PathBuf, Error> {
+            platform::restore_sub_path(self, item, sub_path.as_ref())
+        }
+
+        /// Restores the provided [`TrashItem`] to `dest` instead of its original location.
+        ///
+        /// See: [`restore_to`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn restore_to(&self, item: TrashItem, dest: impl AsRef<std::path::Path>) -> Result<(), Error> {
+            platform::restore_all_to(self, std::iter::once((item, dest.as_ref().to_owned())))
+        }
+
+        /// Restores each of the provided [`TrashItem`]s to the path it's paired with.
+        ///
+        /// See: [`restore_all_to`]
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn restore_all_to<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator<Item = (TrashItem, std::path::PathBuf)>,
+        {
+            platform::restore_all_to(self, items)
+        }
+    }
+
+    /// Removes all files/directories specified by the collection of paths provided as an argument,
+    /// and returns the [`TrashItem`] that each of them became, in the same order.
+    ///
+    /// Unlike with [`delete_all`](crate::delete_all), the items can be passed to [`restore_all`] or
+    /// [`purge_all`] right away, without finding them with [`list`] first. If an error occurs, the items
+    /// that were deleted before remain in the trash, and [`Error::PartialSourceRemoval`] lists them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use std::fs::File;
+    /// use trash::os_limited::{delete_all_with_items, restore_all};
+    /// File::create_new("delete_me_with_items").unwrap();
+    /// let items = delete_all_with_items(["delete_me_with_items"])?;
+    /// assert_eq!(items[0].name, "delete_me_with_items");
+    /// restore_all(items)?;
+    /// std::fs::remove_file("delete_me_with_items").unwrap();
+    /// # }
+    /// # Ok::<(), trash::Error>(())
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn delete_all_with_items<I, T>(paths: I) -> Result<Vec<TrashItem>, Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<std::path::Path>,
+    {
+        DEFAULT_TRASH_CTX.delete_all_with_items(paths)
+    }
 
     /// Returns all [`TrashItem`]s that are currently in the trash.
     ///
@@ -371,7 +741,51 @@ pub mod os_limited {
     /// println!("{:#?}", trash_items);
     /// ```
     pub fn list() -> Result<Vec<TrashItem>, Error> {
-        platform::list()
+        DEFAULT_TRASH_CTX.list()
+    }
+
+    /// Returns all [`TrashItem`]s that are currently in the trash, along with an [`ItemError`] for
+    /// every entry of the trash that could not be read or is malformed.
+    ///
+    /// Unlike [`list`], which logs and skips such entries, this allows to report or repair them.
+    /// Neither of these functions panics on malformed entries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use trash::os_limited::list_with_errors;
+    /// let (trash_items, errors) = list_with_errors().unwrap();
+    /// println!("{:#?}", trash_items);
+    /// for error in errors {
+    ///     eprintln!("{error}");
+    /// }
+    /// ```
+    pub fn list_with_errors() -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+        DEFAULT_TRASH_CTX.list_with_errors()
+    }
+
+    /// Returns an iterator over the [`TrashItem`]s that are currently in the trash, along with an [`ItemError`] for
+    /// every entry of the trash that could not be read or is malformed, like [`list_with_errors`].
+    ///
+    /// The items are read lazily, one trash folder after another, so the first ones are available before the
+    /// whole trash was read, and the iteration can stop early. The items are in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::os_limited::list_iter;
+    /// for item in list_iter().unwrap().take(10) {
+    ///     match item {
+    ///         Ok(item) => println!("{:?}", item.name),
+    ///         Err(error) => eprintln!("{error}"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn list_iter() -> Result<crate::freedesktop::TrashItemIter, Error> {
+        DEFAULT_TRASH_CTX.list_iter()
     }
 
     /// Returns whether the trash is empty or has at least one item.
@@ -389,7 +803,7 @@ pub mod os_limited {
     /// }
     /// ```
     pub fn is_empty() -> Result<bool, Error> {
-        platform::is_empty()
+        DEFAULT_TRASH_CTX.is_empty()
     }
 
     /// Returns all valid trash bins on supported Unix platforms.
@@ -409,11 +823,38 @@ pub mod os_limited {
     /// ```
     #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
     pub fn trash_folders() -> Result<HashSet<std::path::PathBuf>, Error> {
-        platform::trash_folders()
+        DEFAULT_TRASH_CTX.trash_folders()
+    }
+
+    /// Checks all [`trash_folders`] for inconsistencies, and repairs them according to `repair`.
+    ///
+    /// This finds items without a `.trashinfo` file, `.trashinfo` files without an item, `.trashinfo` files
+    /// that can't be parsed, and trash folders that can't be used. Such problems are left behind by
+    /// interrupted trash operations and other programs, and cause items to be missing from [`list`].
+    ///
+    /// Every problem is reported as a [`FsckFinding`](crate::freedesktop::FsckFinding), which carries the
+    /// outcome of its repair. A failing repair doesn't stop the check.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::{freedesktop::FsckRepair, os_limited::fsck};
+    /// for finding in fsck(&FsckRepair::None)? {
+    ///     println!("{:?}", finding.problem);
+    /// }
+    /// # }
+    /// # Ok::<(), trash::Error>(())
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn fsck(repair: &crate::freedesktop::FsckRepair) -> Result<Vec<crate::freedesktop::FsckFinding>, Error> {
+        DEFAULT_TRASH_CTX.fsck(repair)
     }
 
     /// Returns the [`TrashItemMetadata`] for a [`TrashItem`]
     ///
+    /// This only reads the trash, and doesn't measure directories, see [`TrashItemMetadata::disk_usage`].
+    ///
     /// # Example
     ///
     /// ```
@@ -427,6 +868,72 @@ pub mod os_limited {
         platform::metadata(item)
     }
 
+    /// Gives read-only access to the content of the entry at `sub_path` within the provided [`TrashItem`], without
+    /// restoring anything. An empty `sub_path` opens the item itself.
+    ///
+    /// Returns a file for files, the target for symbolic links, and a directory handle for directories.
+    /// `sub_path` is validated like for [`read_trashed_dir`]. Other kinds of files, like FIFOs, can't be opened.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::{freedesktop::TrashedContent, os_limited::{delete_all_with_items, open, purge_all}};
+    ///
+    /// let filename = "trash-open-example";
+    /// std::fs::write(filename, "content").unwrap();
+    /// let item = delete_all_with_items([filename]).unwrap().remove(0);
+    /// if let TrashedContent::File(file) = open(&item, "").unwrap() {
+    ///     assert_eq!(std::io::read_to_string(file).unwrap(), "content");
+    /// }
+    /// purge_all([item]).unwrap();
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn open(
+        item: &TrashItem,
+        sub_path: impl AsRef<std::path::Path>,
+    ) -> Result<crate::freedesktop::TrashedContent, Error> {
+        platform::open(item, sub_path.as_ref())
+    }
+
+    /// Returns the entries of the directory at `sub_path` within the provided directory [`TrashItem`], sorted by
+    /// name, without restoring anything. An empty `sub_path` reads the item itself.
+    ///
+    /// `sub_path` must be relative and must not contain `..`, and it must not lead through symbolic links
+    /// within the item.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::os_limited::{list, read_trashed_dir};
+    /// for item in list().unwrap() {
+    ///     if let Ok(entries) = read_trashed_dir(&item, "") {
+    ///         for entry in entries {
+    ///             println!("{:?}/{:?}: {:?}", item.name, entry.path, entry.size);
+    ///         }
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn read_trashed_dir(
+        item: &TrashItem,
+        sub_path: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<crate::freedesktop::TrashedEntry>, Error> {
+        platform::read_trashed_dir(item, sub_path.as_ref())
+    }
+
+    /// Returns all entries within the provided directory [`TrashItem`], recursively and without restoring
+    /// anything. Every directory is followed by its entries, and the entries of a directory are sorted by name.
+    ///
+    /// Symbolic links are returned, but not followed.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn walk_trashed_dir(item: &TrashItem) -> Result<Vec<crate::freedesktop::TrashedEntry>, Error> {
+        platform::walk_trashed_dir(item)
+    }
+
     /// Deletes all the provided [`TrashItem`]s permanently.
     ///
     /// This function consumes the provided items.
@@ -468,7 +975,103 @@ pub mod os_limited {
         I: IntoIterator,
         <I as IntoIterator>::Item: Borrow<TrashItem>,
     {
-        platform::purge_all(items)
+        DEFAULT_TRASH_CTX.purge_all(items)
+    }
+
+    /// Removes everything from all [`trash_folders`], and returns an [`ItemError`] for every entry that could not
+    /// be removed.
+    ///
+    /// Unlike purging all items of [`list`], this also removes the entries of `$trash/files` that have no
+    /// `.trashinfo` file and the `.trashinfo` files that can't be parsed, resets the directory size cache, and
+    /// continues after entries that can't be removed. The `.trashinfo` files of items that can't be removed are
+    /// kept, so that they are still part of [`list`] and emptying can be retried. Only a cancellation, see
+    /// [`TrashContextExtFreedesktop::set_cancellation_token`](crate::freedesktop::TrashContextExtFreedesktop::set_cancellation_token),
+    /// stops early.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::os_limited::empty;
+    /// for error in empty().unwrap() {
+    ///     eprintln!("{error}");
+    /// }
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn empty() -> Result<Vec<ItemError>, Error> {
+        DEFAULT_TRASH_CTX.empty()
+    }
+
+    /// Like [`empty`] for a single trash folder, which must be one of the [`trash_folders`], like
+    /// `~/.local/share/Trash`.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn empty_folder(path: impl AsRef<std::path::Path>) -> Result<Vec<ItemError>, Error> {
+        DEFAULT_TRASH_CTX.empty_folder(path)
+    }
+
+    /// Purges the oldest items, according to their `time_deleted`, until the size of the trash is at most the
+    /// limit of `quota`, and returns the purged items, oldest first.
+    ///
+    /// The size of an item is the space it occupies on disk, as reported by [`metadata`], which uses the
+    /// directory size cache of the trash folder where possible. Items with an unknown deletion date count as the
+    /// oldest. Depending on the [`QuotaScope`](crate::freedesktop::QuotaScope) of `quota`, the limit applies to
+    /// the total of all [`trash_folders`], or to trash folders on their own. Items that can't be measured or
+    /// purged are skipped and logged.
+    ///
+    /// To enforce a quota after every call of `delete_all`, see
+    /// [`TrashContextExtFreedesktop::set_quota`](crate::freedesktop::TrashContextExtFreedesktop::set_quota).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::{freedesktop::{Quota, QuotaScope}, os_limited::enforce_quota};
+    ///
+    /// let quota = Quota { max_bytes: 10 * 1024 * 1024 * 1024, scope: QuotaScope::EachTrashFolder };
+    /// let purged = enforce_quota(&quota).unwrap();
+    /// println!("Purged {} items", purged.len());
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn enforce_quota(quota: &crate::freedesktop::Quota) -> Result<Vec<TrashItem>, Error> {
+        DEFAULT_TRASH_CTX.enforce_quota(quota)
+    }
+
+    /// Purges the items that have been in the trash for longer than `age`, according to their `time_deleted`,
+    /// and returns them along with an [`ItemError`] for every item that could not be purged.
+    ///
+    /// `options` can limit this to some trash folders or topdirs, and can request a dry run, which only
+    /// returns the items that would be purged. Items whose deletion date is unknown are kept, unless
+    /// [`UnknownDeletionDate::Purge`](crate::freedesktop::UnknownDeletionDate::Purge) is set.
+    ///
+    /// Items that can't be purged are left in the trash, and the others are purged regardless. Only a
+    /// cancellation, see
+    /// [`TrashContextExtFreedesktop::set_cancellation_token`](crate::freedesktop::TrashContextExtFreedesktop::set_cancellation_token),
+    /// stops early.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use std::time::Duration;
+    /// use trash::{freedesktop::RetentionOptions, os_limited::purge_older_than};
+    ///
+    /// // Like `trash-empty 30`
+    /// let (purged, errors) =
+    ///     purge_older_than(Duration::from_secs(30 * 24 * 60 * 60), &RetentionOptions::default()).unwrap();
+    /// println!("Purged {} items", purged.len());
+    /// for error in errors {
+    ///     eprintln!("{error}");
+    /// }
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn purge_older_than(
+        age: std::time::Duration,
+        options: &crate::freedesktop::RetentionOptions,
+    ) -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+        DEFAULT_TRASH_CTX.purge_older_than(age, options)
     }
 
     /// Restores all the provided [`TrashItem`] to their original location.
@@ -520,26 +1123,141 @@ pub mod os_limited {
     where
         I: IntoIterator<Item = TrashItem>,
     {
-        // Check for twins here cause that's pretty platform independent.
-        struct ItemWrapper<'a>(&'a TrashItem);
-        impl PartialEq for ItemWrapper<'_> {
-            fn eq(&self, other: &Self) -> bool {
-                self.0.original_path() == other.0.original_path()
-            }
-        }
-        impl Eq for ItemWrapper<'_> {}
-        impl Hash for ItemWrapper<'_> {
-            fn hash<H: Hasher>(&self, state: &mut H) {
-                self.0.original_path().hash(state);
-            }
-        }
-        let items = items.into_iter().collect::<Vec<_>>();
-        let mut item_set = HashSet::with_capacity(items.len());
-        for item in items.iter() {
-            if !item_set.insert(ItemWrapper(item)) {
-                return Err(Error::RestoreTwins { path: item.original_path(), items });
-            }
-        }
-        platform::restore_all(items)
+        DEFAULT_TRASH_CTX.restore_all(items)
+    }
+
+    /// Restores all the provided [`TrashItem`]s to their original location, and handles collisions according to
+    /// the [`RestoreCollisionStrategy`](crate::freedesktop::RestoreCollisionStrategy) of `options`, instead
+    /// of stopping at the first one like [`restore_all`].
+    ///
+    /// Returns every item that was processed along with what happened to it, in the order they were provided.
+    /// Items with identical `original_path`s are treated like any other collision.
+    ///
+    /// # Errors
+    ///
+    /// With [`RestoreCollisionStrategy::Fail`](crate::freedesktop::RestoreCollisionStrategy::Fail), a collision
+    /// returns a [`RestoreCollision`] error like [`restore_all`]. Any other error stops the restoration as well,
+    /// and leaves the items that weren't processed yet in the trash.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use std::fs::File;
+    /// use trash::freedesktop::{RestoreCollisionStrategy, RestoreOptions, RestoreOutcome};
+    /// use trash::os_limited::{delete_all_with_items, restore_all_with_options};
+    ///
+    /// let filename = "trash-restore_all_with_options-example";
+    /// File::create_new(filename).unwrap();
+    /// let items = delete_all_with_items([filename]).unwrap();
+    /// File::create_new(filename).unwrap();
+    /// let options = RestoreOptions { collision: RestoreCollisionStrategy::Rename };
+    /// for (item, outcome) in restore_all_with_options(items, &options).unwrap() {
+    ///     if let RestoreOutcome::Renamed { path } = outcome {
+    ///         println!("{:?} was restored to {:?}", item.name, path);
+    ///         std::fs::remove_file(path).unwrap();
+    ///     }
+    /// }
+    /// std::fs::remove_file(filename).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// [`RestoreCollision`]: Error::RestoreCollision
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn restore_all_with_options<I>(
+        items: I,
+        options: &crate::freedesktop::RestoreOptions,
+    ) -> Result<Vec<(TrashItem, crate::freedesktop::RestoreOutcome)>, Error>
+    where
+        I: IntoIterator<Item = TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.restore_all_with_options(items, options)
+    }
+
+    /// Restores the entry at `sub_path` within the provided directory [`TrashItem`] to its original location,
+    /// which is `sub_path` under the [`original_path`](TrashItem::original_path) of the item, and returns that path.
+    ///
+    /// The rest of the directory stays in the trash, and so does its `.trashinfo` file. Missing parents of the
+    /// entry are created.
+    ///
+    /// # Errors
+    ///
+    /// `sub_path` must be relative and must not contain `..`, and it must not lead through symbolic links
+    /// within the item. If something already exists at the original location of the entry, a
+    /// [`RestoreCollision`] error is returned, whose only remaining item is the directory.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use trash::os_limited::{delete_all_with_items, purge_all, restore_sub_path};
+    ///
+    /// let dir = "trash-restore_sub_path-example";
+    /// std::fs::create_dir_all(format!("{dir}/src")).unwrap();
+    /// std::fs::write(format!("{dir}/src/main.rs"), "fn main() {}").unwrap();
+    /// let item = delete_all_with_items([dir]).unwrap().remove(0);
+    /// let restored = restore_sub_path(&item, "src/main.rs").unwrap();
+    /// assert!(restored.is_file());
+    /// purge_all([item]).unwrap();
+    /// std::fs::remove_dir_all(dir).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// [`RestoreCollision`]: Error::RestoreCollision
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn restore_sub_path(
+        item: &TrashItem,
+        sub_path: impl AsRef<std::path::Path>,
+    ) -> Result<std::path::PathBuf, Error> {
+        DEFAULT_TRASH_CTX.restore_sub_path(item, sub_path)
+    }
+
+    /// Restores the provided [`TrashItem`] to `dest` instead of its original location, like when the original
+    /// parent doesn't exist anymore, or to restore a copy next to the original.
+    ///
+    /// The parent of `dest` is created if it doesn't exist. If `dest` is on another file system than the trash,
+    /// the item is copied there and then removed from the trash.
+    ///
+    /// # Errors
+    ///
+    /// If something already exists at `dest`, a [`RestoreCollision`] error is returned, and the item stays
+    /// in the trash.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+    /// use std::fs::File;
+    /// use trash::os_limited::{delete_all_with_items, restore_to};
+    ///
+    /// let filename = "trash-restore_to-example";
+    /// File::create_new(filename).unwrap();
+    /// let item = delete_all_with_items([filename]).unwrap().remove(0);
+    /// restore_to(item, "trash-restore_to-example-restored").unwrap();
+    /// std::fs::remove_file("trash-restore_to-example-restored").unwrap();
+    /// # }
+    /// ```
+    ///
+    /// [`RestoreCollision`]: Error::RestoreCollision
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn restore_to(item: TrashItem, dest: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        DEFAULT_TRASH_CTX.restore_to(item, dest)
+    }
+
+    /// Restores each of the provided [`TrashItem`]s to the path it's paired with, like [`restore_to`].
+    ///
+    /// # Errors
+    ///
+    /// If something already exists at one of the paths, a [`RestoreCollision`] error is returned with that path.
+    /// Its `remaining_items` are the items that were not restored in the order they were provided, starting with
+    /// the item that collided, so they can be paired with their paths again.
+    ///
+    /// [`RestoreCollision`]: Error::RestoreCollision
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn restore_all_to<I>(items: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (TrashItem, std::path::PathBuf)>,
+    {
+        DEFAULT_TRASH_CTX.restore_all_to(items)
     }
 }
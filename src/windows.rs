@@ -1,4 +1,4 @@
-use crate::{Error, TrashContext, TrashItem, TrashItemMetadata, TrashItemSize};
+use crate::{Error, ItemError, TrashContext, TrashItem, TrashItemMetadata, TrashItemSize};
 use std::{
     borrow::Borrow,
     ffi::{c_void, OsStr, OsString},
@@ -77,7 +77,7 @@ impl TrashContext {
     }
 }
 
-pub fn list() -> Result<Vec<TrashItem>, Error> {
+pub fn list(_ctx: &TrashContext) -> Result<Vec<TrashItem>, Error> {
     ensure_com_initialized();
     unsafe {
         let mut item_vec = Vec::new();
@@ -126,7 +126,12 @@ pub fn list() -> Result<Vec<TrashItem>, Error> {
     }
 }
 
-pub fn is_empty() -> Result<bool, Error> {
+pub fn list_with_errors(ctx: &TrashContext) -> Result<(Vec<TrashItem>, Vec<ItemError>), Error> {
+    // Items that can't be read make the enumeration fail as a whole.
+    Ok((list(ctx)?, Vec::new()))
+}
+
+pub fn is_empty(_ctx: &TrashContext) -> Result<bool, Error> {
     ensure_com_initialized();
     unsafe {
         let recycle_bin: IShellItem =
@@ -173,10 +178,10 @@ pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
         let item2: IShellItem2 = item.cast()?;
         TrashItemSize::Bytes(unsafe { item2.GetUInt64(&PKEY_Size)? })
     };
-    Ok(TrashItemMetadata { size })
+    Ok(TrashItemMetadata { size, disk_usage: None })
 }
 
-pub fn purge_all<I>(items: I) -> Result<(), Error>
+pub fn purge_all<I>(_ctx: &TrashContext, items: I) -> Result<(), Error>
 where
     I: IntoIterator,
     <I as IntoIterator>::Item: Borrow<TrashItem>,
@@ -200,7 +205,7 @@ where
     }
 }
 
-pub fn restore_all<I>(items: I) -> Result<(), Error>
+pub fn restore_all<I>(_ctx: &TrashContext, items: I) -> Result<(), Error>
 where
     I: IntoIterator<Item = TrashItem>,
 {